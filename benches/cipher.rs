@@ -0,0 +1,46 @@
+// Criterion doesn't support wasm32, so there the benchmark is empty and has no main
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use kotik::utils::cipher::{aes256_cbf8_encrypt, aes256_cfb8_decrypt};
+use std::hint::black_box;
+
+#[path = "../src/packs/fixtures.rs"]
+mod fixtures;
+use fixtures::KEY;
+
+// From a typical json file to a big texture
+const SIZES: [usize; 4] = [1 << 10, 64 << 10, 1 << 20, 4 << 20];
+
+fn cipher(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cipher");
+    // CFB8 runs AES once per byte, big inputs take a while
+    group.sample_size(20);
+
+    for size in SIZES {
+        let data = (0..size).map(|i| i as u8).collect::<Vec<_>>();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        // Cipher takes ownership of the buffer, so copying it isn't measured
+        group.bench_with_input(BenchmarkId::new("encrypt", size), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |data| aes256_cbf8_encrypt(black_box(KEY), data).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("decrypt", size), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |data| aes256_cfb8_decrypt(black_box(KEY), data).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, cipher);
+criterion_main!(benches);
@@ -0,0 +1,151 @@
+// Criterion doesn't support wasm32, so there the benchmark is empty and has no main
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use kotik::packs::encryption::decrypt::{decrypt, DecryptOptions};
+use kotik::packs::encryption::encrypt::{encrypt, EncryptOptions};
+use kotik::packs::pack_encryption::ProcessingOptions;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+#[path = "../src/packs/fixtures.rs"]
+mod fixtures;
+use fixtures::{KEY, MANIFEST};
+
+const FILE_COUNTS: [usize; 2] = [1000, 5000];
+// 0 is one thread per CPU core
+const JOBS: [usize; 2] = [1, 0];
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Half json, half textures of a few KiB, spread over folders like in real packs.
+// Returns total size of files to encrypt
+fn generate_pack(root: &Path, files: usize) -> u64 {
+    fs::write(root.join("manifest.json"), MANIFEST).unwrap();
+
+    let mut bytes = 0;
+    for i in 0..files {
+        let folder = root.join(format!("folder_{}", i % 50));
+        fs::create_dir_all(&folder).unwrap();
+
+        let (path, content) = if i % 2 == 0 {
+            let content = format!(
+                "{{\n  // entity {}\n  \"format_version\": \"1.10.0\",\n  \"description\": \"{}\"\n}}",
+                i,
+                "x".repeat(2048)
+            );
+            (
+                folder.join(format!("file_{}.json", i)),
+                content.into_bytes(),
+            )
+        } else {
+            let mut content = PNG_SIGNATURE.to_vec();
+            content.extend((0..4096).map(|j| (i + j) as u8));
+            (folder.join(format!("file_{}.png", i)), content)
+        };
+        bytes += content.len() as u64;
+        fs::write(path, content).unwrap();
+    }
+    bytes
+}
+
+fn copy_pack(source: &Path, target: &Path) {
+    for entry in fs::read_dir(source).unwrap() {
+        let entry = entry.unwrap();
+        let target = target.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            fs::create_dir_all(&target).unwrap();
+            copy_pack(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+// Encrypt and decrypt work in place, so every iteration gets a fresh copy.
+// Copying isn't measured
+fn fresh_copy(source: &Path) -> TempDir {
+    let temp = TempDir::new().unwrap();
+    copy_pack(source, temp.path());
+    temp
+}
+
+fn pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    group.sample_size(10);
+
+    for files in FILE_COUNTS {
+        let plain = TempDir::new().unwrap();
+        let bytes = generate_pack(plain.path(), files);
+        let encrypted = fresh_copy(plain.path());
+        encrypt(
+            KEY,
+            encrypted.path().as_os_str().to_os_string(),
+            &EncryptOptions::default(),
+        )
+        .unwrap();
+        group.throughput(Throughput::Bytes(bytes));
+
+        for jobs in JOBS {
+            let processing = ProcessingOptions {
+                jobs,
+                ..Default::default()
+            };
+            let id = format!("{}_files/{}_jobs", files, jobs);
+
+            let options = EncryptOptions {
+                processing: processing.clone(),
+                ..Default::default()
+            };
+            group.bench_function(BenchmarkId::new("encrypt", &id), |b| {
+                b.iter_batched(
+                    || fresh_copy(plain.path()),
+                    |pack| {
+                        encrypt(KEY, pack.path().as_os_str().to_os_string(), &options).unwrap();
+                        // Removing is left to the next setup
+                        pack
+                    },
+                    BatchSize::PerIteration,
+                )
+            });
+
+            let options = EncryptOptions {
+                minify: true,
+                processing: processing.clone(),
+                ..Default::default()
+            };
+            group.bench_function(BenchmarkId::new("minify_encrypt", &id), |b| {
+                b.iter_batched(
+                    || fresh_copy(plain.path()),
+                    |pack| {
+                        encrypt(KEY, pack.path().as_os_str().to_os_string(), &options).unwrap();
+                        pack
+                    },
+                    BatchSize::PerIteration,
+                )
+            });
+
+            let options = DecryptOptions {
+                processing,
+                ..Default::default()
+            };
+            group.bench_function(BenchmarkId::new("decrypt", &id), |b| {
+                b.iter_batched(
+                    || fresh_copy(encrypted.path()),
+                    |pack| {
+                        decrypt(KEY, pack.path().as_os_str().to_os_string(), &options).unwrap();
+                        pack
+                    },
+                    BatchSize::PerIteration,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+// Header is generated into OUT_DIR, so builds never touch the source tree.
+// Committed include/kotik.h is updated only on request with
+// KOTIK_UPDATE_HEADER=1, tests check that it's up to date
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=KOTIK_UPDATE_HEADER");
+
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("Invalid cbindgen.toml");
+    let header = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/ffi.rs")
+        .generate()
+        .expect("Unable to generate C header");
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not set");
+    header.write_to_file(std::path::Path::new(&out_dir).join("kotik.h"));
+    if std::env::var_os("KOTIK_UPDATE_HEADER").is_some_and(|value| value == "1") {
+        header.write_to_file("include/kotik.h");
+    }
+}
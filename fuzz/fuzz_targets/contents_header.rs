@@ -0,0 +1,19 @@
+#![no_main]
+
+use kotik::packs::contents::{
+    generate_contents_header, parse_contents_header, CONTENTS_HEADER_SIZE,
+};
+use libfuzzer_sys::fuzz_target;
+
+// First 256 bytes of contents.json from untrusted pack
+fuzz_target!(|data: &[u8]| {
+    let Ok(header) = parse_contents_header(data) else {
+        return;
+    };
+
+    // Parsed UUID always fits in the header, so it must survive regeneration
+    let generated =
+        generate_contents_header(&header.uuid).expect("parsed UUID must fit in the header");
+    assert_eq!(generated.len(), CONTENTS_HEADER_SIZE);
+    assert_eq!(parse_contents_header(&generated).unwrap().uuid, header.uuid);
+});
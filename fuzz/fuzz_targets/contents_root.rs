@@ -0,0 +1,58 @@
+#![no_main]
+
+use kotik::packs::contents::{find_unsafe_paths, generate_contents_header, generate_contents_root};
+use kotik::packs::encryption::decrypt::decrypt_contents;
+use kotik::packs::storage::MemoryStorage;
+use kotik::utils::cipher::{aes256_cbf8_encrypt, aes256_cfb8_decrypt};
+use libfuzzer_sys::fuzz_target;
+use std::path::{Component, Path, PathBuf};
+
+#[path = "../../src/packs/fixtures.rs"]
+mod fixtures;
+use fixtures::{KEY, UUID};
+
+// Input is decrypted json of contents.json. It's encrypted with a known key
+// first, so fuzzer controls the json instead of fighting the cipher
+fuzz_target!(|data: &[u8]| {
+    let mut contents_file = generate_contents_header(UUID).unwrap();
+    contents_file.extend(aes256_cbf8_encrypt(KEY, data.to_vec()).unwrap());
+
+    let Ok((header, root, decrypted)) = decrypt_contents(KEY, &contents_file) else {
+        return;
+    };
+    assert_eq!(header.uuid, UUID);
+    assert_eq!(decrypted, data);
+
+    // Keys of untrusted entries must fail with error, not panic
+    for item in &root.content {
+        if let Some(key) = &item.key {
+            let _ = aes256_cfb8_decrypt(key, b"sample".to_vec());
+        }
+    }
+
+    // Paths, that pass the check, can't leave the pack lexically
+    let unsafe_paths = find_unsafe_paths(&MemoryStorage::new(), &root.content).unwrap();
+    for item in &root.content {
+        if unsafe_paths.iter().any(|path| path.path == item.path) {
+            continue;
+        }
+        assert!(Path::new(&item.path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir)));
+    }
+
+    // Listed paths go through the same conversion as paths of a pack
+    let paths = root
+        .content
+        .iter()
+        .map(|item| PathBuf::from(&item.path))
+        .collect::<Vec<_>>();
+    for (item, generated) in root.content.iter().zip(generate_contents_root(&paths)) {
+        if generated.path.ends_with('/') {
+            assert!(generated.key.is_none());
+        }
+        if !cfg!(windows) {
+            assert_eq!(generated.path, item.path);
+        }
+    }
+});
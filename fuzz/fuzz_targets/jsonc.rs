@@ -0,0 +1,33 @@
+#![no_main]
+
+use kotik::utils::jsonc::parse_jsonc;
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(content) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let Ok(value) = parse_jsonc(content) else {
+        // Plain json is a subset of jsonc, so it must never be rejected
+        // unless it's nested too deep for our parser
+        if let Ok(value) = serde_json::from_str::<Value>(content) {
+            assert!(depth(&value) >= 128, "valid json rejected: {:?}", content);
+        }
+        return;
+    };
+
+    // Minify writes this string, so it must be read back the same way
+    let minified = value.to_string();
+    assert_eq!(parse_jsonc(&minified).unwrap(), value);
+    assert_eq!(serde_json::from_str::<Value>(&minified).unwrap(), value);
+});
+
+fn depth(value: &Value) -> usize {
+    match value {
+        Value::Array(items) => 1 + items.iter().map(depth).max().unwrap_or(0),
+        Value::Object(map) => 1 + map.values().map(depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
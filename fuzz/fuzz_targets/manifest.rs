@@ -0,0 +1,15 @@
+#![no_main]
+
+use kotik::packs::manifest::parse_manifest;
+use libfuzzer_sys::fuzz_target;
+
+// manifest.json from untrusted pack, parsing and validation must never panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(content) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Ok(manifest) = parse_manifest(content) {
+        let _ = manifest.validate();
+    }
+});
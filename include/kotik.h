@@ -0,0 +1,95 @@
+#ifndef KOTIK_H
+#define KOTIK_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Strip comments and whitespaces from .json and .material files
+#define KOTIK_MINIFY 1
+
+// Run pack linter before encryption, lint errors abort it
+#define KOTIK_LINT (1 << 1)
+
+// Encrypt even if pack looks already encrypted
+#define KOTIK_FORCE (1 << 2)
+
+// Result of every kotik function. Values never change between versions
+typedef enum KotikStatus {
+  KOTIK_STATUS_OK = 0,
+  // Null pointer or string that isn't valid UTF-8
+  KOTIK_STATUS_INVALID_ARGUMENT = 1,
+  // Key isn't 32 bytes long
+  KOTIK_STATUS_INVALID_KEY = 2,
+  // manifest.json is missing or invalid
+  KOTIK_STATUS_MANIFEST_ERROR = 3,
+  // Pack looks already encrypted, KOTIK_FORCE encrypts it anyway
+  KOTIK_STATUS_ALREADY_ENCRYPTED = 4,
+  // contents.json lists paths outside of the pack
+  KOTIK_STATUS_UNSAFE_PATHS = 5,
+  KOTIK_STATUS_IO_ERROR = 6,
+  // Buffer isn't a valid zip archive
+  KOTIK_STATUS_ARCHIVE_ERROR = 7,
+  // contents.json can't be read, probably key is wrong
+  KOTIK_STATUS_CONTENTS_ERROR = 8,
+  // Pack linter found errors, only with KOTIK_LINT
+  KOTIK_STATUS_LINT_ERROR = 9,
+  // Some files failed to encrypt or decrypt
+  KOTIK_STATUS_PROCESSING_ERROR = 10,
+  // Internal error, please report it
+  KOTIK_STATUS_PANIC = 11,
+  // Any other error, kotik_last_error_message describes it
+  KOTIK_STATUS_ERROR = 12,
+} KotikStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Encrypts pack folder in place with 32 bytes long key. flags is a
+// combination of KOTIK_MINIFY, KOTIK_LINT and KOTIK_FORCE
+//
+// # Safety
+// key and path must be null or nul-terminated strings
+enum KotikStatus kotik_encrypt_dir(const char *key, const char *path, uint32_t flags);
+
+// Decrypts pack folder in place, contents.json is written decrypted
+//
+// # Safety
+// key and path must be null or nul-terminated strings
+enum KotikStatus kotik_decrypt_dir(const char *key, const char *path);
+
+// Encrypts pack given as zip archive (.mcpack) with manifest.json in its
+// root. Encrypted archive is returned through out_data and out_len and must
+// be released with kotik_free_buffer. On error they are set to null and 0
+//
+// # Safety
+// key must be null or nul-terminated string, data must point to len
+// readable bytes, out_data and out_len must be null or writable
+enum KotikStatus kotik_encrypt_buffer(const char *key,
+                                      const uint8_t *data,
+                                      size_t len,
+                                      uint32_t flags,
+                                      uint8_t **out_data,
+                                      size_t *out_len);
+
+// Releases buffer returned by kotik_encrypt_buffer, null is ignored
+//
+// # Safety
+// data and len must be exactly as returned by kotik, buffer can be
+// released only once
+void kotik_free_buffer(uint8_t *data, size_t len);
+
+// Message of the last error on the calling thread, null if the last call
+// succeeded. Pointer is valid until the next kotik call on the same thread
+const char *kotik_last_error_message(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* KOTIK_H */
@@ -0,0 +1,225 @@
+// C interface for embedding kotik into server software written in other
+// languages. Header include/kotik.h is generated from this file by cbindgen,
+// so doc comments here end up in the header
+
+use crate::packs::encryption::decrypt::{decrypt, DecryptOptions};
+use crate::packs::encryption::encrypt::{encrypt, encrypt_storage, EncryptOptions};
+use crate::packs::pack_encryption::{panic_message, PackEncryptionError};
+use crate::packs::storage::ZipStorage;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString, OsString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Result of every kotik function. Values never change between versions
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KotikStatus {
+    Ok = 0,
+    /// Null pointer or string that isn't valid UTF-8
+    InvalidArgument = 1,
+    /// Key isn't 32 bytes long
+    InvalidKey = 2,
+    /// manifest.json is missing or invalid
+    ManifestError = 3,
+    /// Pack looks already encrypted, KOTIK_FORCE encrypts it anyway
+    AlreadyEncrypted = 4,
+    /// contents.json lists paths outside of the pack
+    UnsafePaths = 5,
+    IoError = 6,
+    /// Buffer isn't a valid zip archive
+    ArchiveError = 7,
+    /// contents.json can't be read, probably key is wrong
+    ContentsError = 8,
+    /// Pack linter found errors, only with KOTIK_LINT
+    LintError = 9,
+    /// Some files failed to encrypt or decrypt
+    ProcessingError = 10,
+    /// Internal error, please report it
+    Panic = 11,
+    /// Any other error, kotik_last_error_message describes it
+    Error = 12,
+}
+
+/// Strip comments and whitespaces from .json and .material files
+pub const KOTIK_MINIFY: u32 = 1;
+/// Run pack linter before encryption, lint errors abort it
+pub const KOTIK_LINT: u32 = 1 << 1;
+/// Encrypt even if pack looks already encrypted
+pub const KOTIK_FORCE: u32 = 1 << 2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+type Failure = (KotikStatus, String);
+
+impl From<PackEncryptionError> for Failure {
+    fn from(error: PackEncryptionError) -> Self {
+        let status = match &error {
+            PackEncryptionError::CipherError(_) => KotikStatus::InvalidKey,
+            PackEncryptionError::ManifestError(_) => KotikStatus::ManifestError,
+            PackEncryptionError::AlreadyEncrypted(_) => KotikStatus::AlreadyEncrypted,
+            PackEncryptionError::UnsafePaths(_) => KotikStatus::UnsafePaths,
+            PackEncryptionError::FileSystemError(_) | PackEncryptionError::FileError(_) => {
+                KotikStatus::IoError
+            }
+            PackEncryptionError::ArchiveError(_) => KotikStatus::ArchiveError,
+            PackEncryptionError::ContentsDecodingError(_) | PackEncryptionError::JsonError(_) => {
+                KotikStatus::ContentsError
+            }
+            PackEncryptionError::LintError(_) => KotikStatus::LintError,
+            PackEncryptionError::ProcessingError(_)
+            | PackEncryptionError::Cancelled(_)
+            | PackEncryptionError::PartiallyEncrypted(_)
+            | PackEncryptionError::WorkerPanicked(..) => KotikStatus::ProcessingError,
+            _ => KotikStatus::Error,
+        };
+        (status, error.to_string())
+    }
+}
+
+// Runs body, remembering its error message for kotik_last_error_message.
+// Panics must not unwind into foreign code
+fn run(body: impl FnOnce() -> Result<(), Failure>) -> KotikStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => (KotikStatus::Ok, None),
+        Ok(Err((status, message))) => (status, Some(message)),
+        Err(payload) => (KotikStatus::Panic, Some(panic_message(payload.as_ref()))),
+    };
+
+    // Interior nul bytes can't be passed to C, so they are dropped
+    let message = message.map(|message| CString::new(message.replace('\0', "")).unwrap());
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    status
+}
+
+unsafe fn read_str<'a>(pointer: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if pointer.is_null() {
+        return Err((KotikStatus::InvalidArgument, format!("{} is null", name)));
+    }
+    CStr::from_ptr(pointer).to_str().map_err(|_| {
+        (
+            KotikStatus::InvalidArgument,
+            format!("{} is not valid UTF-8", name),
+        )
+    })
+}
+
+unsafe fn read_key<'a>(pointer: *const c_char) -> Result<&'a str, Failure> {
+    let key = read_str(pointer, "key")?;
+    if key.len() != 32 {
+        return Err((
+            KotikStatus::InvalidKey,
+            format!("Key must be 32 bytes long, got {}", key.len()),
+        ));
+    }
+    Ok(key)
+}
+
+fn encrypt_options(flags: u32) -> EncryptOptions {
+    EncryptOptions {
+        minify: flags & KOTIK_MINIFY != 0,
+        lint: flags & KOTIK_LINT != 0,
+        force: flags & KOTIK_FORCE != 0,
+        ..Default::default()
+    }
+}
+
+/// Encrypts pack folder in place with 32 bytes long key. flags is a
+/// combination of KOTIK_MINIFY, KOTIK_LINT and KOTIK_FORCE
+///
+/// # Safety
+/// key and path must be null or nul-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn kotik_encrypt_dir(
+    key: *const c_char,
+    path: *const c_char,
+    flags: u32,
+) -> KotikStatus {
+    run(|| {
+        let key = read_key(key)?;
+        let path = read_str(path, "path")?;
+        encrypt(key, OsString::from(path), &encrypt_options(flags))?;
+        Ok(())
+    })
+}
+
+/// Decrypts pack folder in place, contents.json is written decrypted
+///
+/// # Safety
+/// key and path must be null or nul-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn kotik_decrypt_dir(key: *const c_char, path: *const c_char) -> KotikStatus {
+    run(|| {
+        let key = read_key(key)?;
+        let path = read_str(path, "path")?;
+        decrypt(key, OsString::from(path), &DecryptOptions::default())?;
+        Ok(())
+    })
+}
+
+/// Encrypts pack given as zip archive (.mcpack) with manifest.json in its
+/// root. Encrypted archive is returned through out_data and out_len and must
+/// be released with kotik_free_buffer. On error they are set to null and 0
+///
+/// # Safety
+/// key must be null or nul-terminated string, data must point to len
+/// readable bytes, out_data and out_len must be null or writable
+#[no_mangle]
+pub unsafe extern "C" fn kotik_encrypt_buffer(
+    key: *const c_char,
+    data: *const u8,
+    len: usize,
+    flags: u32,
+    out_data: *mut *mut u8,
+    out_len: *mut usize,
+) -> KotikStatus {
+    run(|| {
+        if out_data.is_null() || out_len.is_null() {
+            return Err((
+                KotikStatus::InvalidArgument,
+                String::from("out_data or out_len is null"),
+            ));
+        }
+        *out_data = ptr::null_mut();
+        *out_len = 0;
+
+        let key = read_key(key)?;
+        if data.is_null() {
+            return Err((KotikStatus::InvalidArgument, String::from("data is null")));
+        }
+
+        let storage = ZipStorage::from_bytes(std::slice::from_raw_parts(data, len))?;
+        encrypt_storage(key, &storage, &encrypt_options(flags))?;
+        let bytes = storage.to_bytes()?.into_boxed_slice();
+
+        *out_len = bytes.len();
+        *out_data = Box::into_raw(bytes).cast::<u8>();
+        Ok(())
+    })
+}
+
+/// Releases buffer returned by kotik_encrypt_buffer, null is ignored
+///
+/// # Safety
+/// data and len must be exactly as returned by kotik, buffer can be
+/// released only once
+#[no_mangle]
+pub unsafe extern "C" fn kotik_free_buffer(data: *mut u8, len: usize) {
+    if !data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    }
+}
+
+/// Message of the last error on the calling thread, null if the last call
+/// succeeded. Pointer is valid until the next kotik call on the same thread
+#[no_mangle]
+pub extern "C" fn kotik_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
@@ -0,0 +1,9 @@
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod marketplace;
+pub mod packs;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,26 +1,31 @@
-use crate::utils::cli::{get_input, handle_user_input};
-
-mod marketplace;
-mod packs;
-mod utils;
+use kotik::utils::cli::{handle_interrupts, run_command};
+use kotik::utils::repl::run_repl;
+use std::env;
+use std::process::exit;
 
 const BLUE: &str = "\x1B[0;36m";
-const RED: &str = "\x1b[0;31m";
 const RESET: &str = "\x1b[0m";
 
 fn main() {
+    if let Err(e) = handle_interrupts() {
+        eprintln!("Can't handle Ctrl-C: {}", e);
+    }
+
+    // With arguments Kotik runs single command and exits,
+    // otherwise it starts interactive mode
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        exit(if run_command(&args) { 0 } else { 1 });
+    }
+
     println!(
         "{}Welcome to Kotik - Pack encryption utility for Minecraft Bedrock{}\n",
         BLUE, RESET
     );
 
-    loop {
-        let mut input = String::new();
-        get_input(
-            "Enter command or use 'help' for available commands:",
-            &mut input,
-        );
-
-        handle_user_input(input.trim()).unwrap_or_else(|e| println!("{}{}{}", RED, e, RESET));
+    if let Err(e) = run_repl() {
+        eprintln!("Interactive mode failed: {}", e);
+        exit(1);
     }
 }
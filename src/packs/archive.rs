@@ -0,0 +1,174 @@
+use crate::packs::encryption::encrypt::{encrypt_storage, EncryptOptions, EncryptReport};
+use crate::packs::manifest::read_storage_manifest;
+use crate::packs::pack_encryption::{error_chain, PackEncryptionError};
+use crate::packs::storage::{PackStorage, SubfolderStorage, ZipStorage};
+use crate::utils::cipher::generate_random_key;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+// Archive encryption is for .mcaddon/.mcpack files. Every pack inside
+// archive gets its own contents.json and random key
+
+// .mcaddon often bundles packs as archives instead of folders. Only one level
+// of nesting is supported, archives inside them are left as they are
+const NESTED_ARCHIVE_EXTENSIONS: [&str; 2] = ["mcpack", "zip"];
+
+#[derive(Debug, Serialize)]
+pub struct EncryptedPack {
+    // Pack folder inside the archive, empty for .mcpack with manifest in the root.
+    // Packs of nested archives start with archive path, like rp.mcpack/folder
+    pub path: String,
+    pub uuid: String,
+    pub key: String,
+    pub report: EncryptReport,
+}
+
+// Returns path of repackaged archive and keys for every encrypted pack
+pub fn encrypt_archive(
+    archive_path: &Path,
+    options: &EncryptOptions,
+) -> Result<(PathBuf, Vec<EncryptedPack>), PackEncryptionError> {
+    // Whole archive is encrypted in memory, nothing is written until the end
+    let storage = ZipStorage::open(archive_path)?;
+    let packs = encrypt_archive_storage(&storage, options)?;
+
+    let output_path = encrypted_archive_path(archive_path);
+    storage.save(&output_path)?;
+
+    Ok((output_path, packs))
+}
+
+// Same as encrypt_archive, for archive that is already open, e.g. received
+// as bytes. Storage holds encrypted archive afterwards
+pub fn encrypt_archive_storage(
+    storage: &ZipStorage,
+    options: &EncryptOptions,
+) -> Result<Vec<EncryptedPack>, PackEncryptionError> {
+    let relative_paths = storage
+        .list_entries()
+        .map_err(PackEncryptionError::FileSystemError)?;
+    let pack_roots = find_pack_roots(&relative_paths);
+
+    let mut packs = encrypt_packs(storage, &pack_roots, options)?;
+    for archive_path in find_nested_archives(&relative_paths, &pack_roots) {
+        let name = archive_path.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+        let nested = storage
+            .read(&archive_path)
+            .map_err(PackEncryptionError::FileSystemError)?;
+        let nested = ZipStorage::from_bytes(&nested).map_err(|e| {
+            PackEncryptionError::DataCollectionError(format!(
+                "Can't open archive {}: {}",
+                name,
+                error_chain(&e)
+            ))
+        })?;
+
+        let nested_roots = find_pack_roots(
+            &nested
+                .list_entries()
+                .map_err(PackEncryptionError::FileSystemError)?,
+        );
+        // Archives without packs aren't touched
+        if nested_roots.is_empty() {
+            continue;
+        }
+
+        for mut pack in encrypt_packs(&nested, &nested_roots, options)? {
+            pack.path = if pack.path.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", name, pack.path)
+            };
+            packs.push(pack);
+        }
+        storage
+            .write(&archive_path, &nested.to_bytes()?)
+            .map_err(PackEncryptionError::FileSystemError)?;
+    }
+
+    if packs.is_empty() {
+        return Err(PackEncryptionError::DataCollectionError(String::from(
+            "No packs with manifest.json found in archive or in .mcpack/.zip archives inside it",
+        )));
+    }
+    Ok(packs)
+}
+
+fn encrypt_packs(
+    storage: &ZipStorage,
+    pack_roots: &[PathBuf],
+    options: &EncryptOptions,
+) -> Result<Vec<EncryptedPack>, PackEncryptionError> {
+    let mut packs = Vec::with_capacity(pack_roots.len());
+    for pack_root in pack_roots {
+        let pack = SubfolderStorage::new(storage, pack_root);
+        let uuid = read_storage_manifest(&pack)
+            .map_err(PackEncryptionError::ManifestError)?
+            .header
+            .uuid;
+        let key = generate_random_key();
+
+        let report = encrypt_storage(&key, &pack, options)?;
+
+        packs.push(EncryptedPack {
+            path: pack_root.to_string_lossy().replace(MAIN_SEPARATOR, "/"),
+            uuid,
+            key,
+            report,
+        });
+    }
+    Ok(packs)
+}
+
+// Archives outside of packs. Inside a pack they are just pack files
+fn find_nested_archives(relative_paths: &[PathBuf], pack_roots: &[PathBuf]) -> Vec<PathBuf> {
+    relative_paths
+        .iter()
+        .filter(|path| !path.to_string_lossy().ends_with(MAIN_SEPARATOR))
+        .filter(|path| {
+            path.extension().is_some_and(|extension| {
+                NESTED_ARCHIVE_EXTENSIONS
+                    .iter()
+                    .any(|nested| extension.eq_ignore_ascii_case(nested))
+            })
+        })
+        .filter(|path| !pack_roots.iter().any(|root| path.starts_with(root)))
+        .cloned()
+        .collect()
+}
+
+// Every folder with manifest.json is a pack. Packs can't be nested,
+// so we stop at the first manifest on the way down
+fn find_pack_roots(relative_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut manifest_parents = relative_paths
+        .iter()
+        .filter(|path| path.file_name().is_some_and(|name| name == "manifest.json"))
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .collect::<Vec<_>>();
+
+    // Shortest paths first, so outer packs are found before anything inside them
+    manifest_parents.sort_by_key(|path| path.components().count());
+
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for parent in manifest_parents {
+        if !roots.iter().any(|root| parent.starts_with(root)) {
+            roots.push(parent);
+        }
+    }
+    roots
+}
+
+// pack.mcaddon -> pack_encrypted.mcaddon
+fn encrypted_archive_path(archive_path: &Path) -> PathBuf {
+    let mut file_name = archive_path
+        .file_stem()
+        .map(OsString::from)
+        .unwrap_or_default();
+    file_name.push("_encrypted");
+    if let Some(extension) = archive_path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    archive_path.with_file_name(file_name)
+}
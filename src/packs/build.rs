@@ -0,0 +1,320 @@
+use crate::packs::config::{read_config, ConfigError, ExportTarget};
+use crate::packs::encryption::encrypt::{encrypt, EncryptOptions, EncryptReport};
+use crate::packs::pack_encryption::{list_relative_paths, write_file, PackEncryptionError};
+use crate::packs::storage::{write_archive, DirectoryStorage};
+use crate::utils::cipher::generate_random_key;
+use glob::{MatchOptions, Pattern};
+use serde::Serialize;
+use std::env::temp_dir;
+use std::fmt::{Display, Formatter};
+use std::fs::{copy, create_dir_all, remove_dir_all};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+// Build is encryption of a copy of the pack, described by a profile
+// from kotik.toml. Source folder is never modified
+
+// Temp folder, that is removed even if encryption failed
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> std::io::Result<Self> {
+        let temp = TempDir(temp_dir().join(format!("kotik-{}", generate_random_key())));
+        create_dir_all(&temp.0)?;
+        Ok(temp)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    ConfigError(ConfigError),
+    EncryptionError(PackEncryptionError),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::ConfigError(_) => write!(f, "Can't use project config"),
+            // Encryption errors already say what went wrong
+            BuildError::EncryptionError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::ConfigError(e) => Some(e),
+            BuildError::EncryptionError(e) => e.source(),
+        }
+    }
+}
+
+impl From<ConfigError> for BuildError {
+    fn from(e: ConfigError) -> Self {
+        BuildError::ConfigError(e)
+    }
+}
+
+impl From<PackEncryptionError> for BuildError {
+    fn from(e: PackEncryptionError) -> Self {
+        BuildError::EncryptionError(e)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildReport {
+    pub profile: String,
+    pub key: String,
+    // Files left out by exclude patterns
+    pub excluded: usize,
+    pub artifacts: Vec<PathBuf>,
+    pub key_file: PathBuf,
+    pub report: EncryptReport,
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    // So * doesn't match across folders, and ** does
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+// Path is excluded if it or any folder above it matches
+fn is_excluded(rel_path: &str, patterns: &[Pattern]) -> bool {
+    let rel_path = rel_path.trim_end_matches('/');
+    rel_path
+        .match_indices('/')
+        .map(|(index, _)| &rel_path[..index])
+        .chain([rel_path])
+        .any(|path| patterns.iter().any(|p| p.matches_with(path, MATCH_OPTIONS)))
+}
+
+// Copies files of source, that aren't excluded, returns number of excluded files.
+// Folders are created only for files, so empty ones are left out
+fn copy_pack(source: &Path, target: &Path, patterns: &[Pattern]) -> std::io::Result<usize> {
+    create_dir_all(target)?;
+
+    let mut excluded = 0;
+    for rel_path in list_relative_paths(&source.as_os_str().to_os_string())? {
+        let name = rel_path.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+        if name.ends_with('/') {
+            continue;
+        }
+        if is_excluded(&name, patterns) {
+            excluded += 1;
+            continue;
+        }
+
+        if let Some(parent) = target.join(&rel_path).parent() {
+            create_dir_all(parent)?;
+        }
+        copy(source.join(&rel_path), target.join(&rel_path))?;
+    }
+    Ok(excluded)
+}
+
+// Stages:
+// 1. Reading config and resolving profile and key
+// 2. Copying source without excluded files to temp folder
+// 3. Encrypting the copy
+// 4. Exporting targets and key file to output folder
+//
+// Lint and minify settings of options are replaced by the profile ones
+pub fn build(
+    config_path: &Path,
+    profile_name: Option<&str>,
+    options: &EncryptOptions,
+) -> Result<BuildReport, BuildError> {
+    let config = read_config(config_path)?;
+    let (profile_name, profile) = config.profile(profile_name)?;
+
+    // Paths in config are relative to it
+    let base = config_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let source = base.join(&profile.source);
+    if !source.is_dir() {
+        return Err(PackEncryptionError::DataCollectionError(format!(
+            "Source folder {} doesn't exist",
+            source.display()
+        ))
+        .into());
+    }
+    let output = base.join(
+        profile
+            .output
+            .clone()
+            .unwrap_or_else(|| Path::new("build").join(&profile_name)),
+    );
+    let name = match &profile.name {
+        Some(name) => name.clone(),
+        None => source
+            .canonicalize()
+            .map_err(PackEncryptionError::FileSystemError)?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("pack")),
+    };
+
+    let key = match profile.key.resolve(base)? {
+        Some(key) => key,
+        None => generate_random_key(),
+    };
+
+    let mut patterns = profile.exclude_patterns()?;
+    // Config and previous builds must not get into the pack, when they live inside source
+    create_dir_all(&output).map_err(PackEncryptionError::FileSystemError)?;
+    let source = source
+        .canonicalize()
+        .map_err(PackEncryptionError::FileSystemError)?;
+    for path in [config_path, output.as_path()] {
+        let Ok(path) = path.canonicalize() else {
+            continue;
+        };
+        if let Ok(rel_path) = path.strip_prefix(&source) {
+            let rel_path = rel_path.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+            patterns.push(Pattern::new(&Pattern::escape(&rel_path)).unwrap());
+        }
+    }
+
+    // Folder target is replaced on every build, so it must never contain the source
+    let folder_artifact = output.join(&name);
+    if profile.targets.contains(&ExportTarget::Folder)
+        && folder_artifact
+            .canonicalize()
+            .is_ok_and(|artifact| source.starts_with(artifact))
+    {
+        return Err(PackEncryptionError::DataCollectionError(format!(
+            "Output folder {} contains the source folder",
+            folder_artifact.display()
+        ))
+        .into());
+    }
+
+    let temp = TempDir::new().map_err(PackEncryptionError::FileSystemError)?;
+    let excluded =
+        copy_pack(&source, &temp.0, &patterns).map_err(PackEncryptionError::FileSystemError)?;
+
+    let options = EncryptOptions {
+        lint: profile.lint,
+        minify: profile.minify,
+        strict_json: profile.strict_json,
+        ..options.clone()
+    };
+    let report = encrypt(&key, temp.0.clone().into_os_string(), &options)?;
+
+    let mut artifacts = Vec::with_capacity(profile.targets.len());
+    for target in &profile.targets {
+        let artifact = match target {
+            ExportTarget::Folder => folder_artifact.clone(),
+            ExportTarget::Mcpack => output.join(format!("{}.mcpack", name)),
+            ExportTarget::Zip => output.join(format!("{}.zip", name)),
+        };
+
+        if *target == ExportTarget::Folder {
+            if artifact.is_dir() {
+                remove_dir_all(&artifact).map_err(PackEncryptionError::FileSystemError)?;
+            }
+            copy_pack(&temp.0, &artifact, &[]).map_err(PackEncryptionError::FileSystemError)?;
+        } else {
+            write_archive(&DirectoryStorage::new(&temp.0), &artifact)?;
+        }
+        artifacts.push(artifact);
+    }
+
+    // Servers expect the key next to the pack
+    let key_file = output.join(format!("{}.key", name));
+    write_file(key.as_bytes(), &key_file).map_err(PackEncryptionError::FileSystemError)?;
+
+    Ok(BuildReport {
+        profile: profile_name,
+        key,
+        excluded,
+        artifacts,
+        key_file,
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn patterns(patterns: &[&str]) -> Vec<Pattern> {
+        patterns
+            .iter()
+            .map(|pattern| Pattern::new(pattern).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn excluded_paths() {
+        let patterns = patterns(&["**/*.psd", "*.md", ".git", "textures/raw"]);
+
+        for path in [
+            "textures/stone.psd",
+            "stone.psd",
+            "README.md",
+            ".git/config",
+            ".git/objects/ab/cdef",
+            "textures/raw/",
+            "textures/raw/stone.png",
+        ] {
+            assert!(is_excluded(path, &patterns), "{}", path);
+        }
+        for path in [
+            // * doesn't match across folders
+            "docs/README.md",
+            "textures/stone.png",
+            "textures/raw_stone.png",
+            "sub/.git/config",
+            "manifest.json",
+        ] {
+            assert!(!is_excluded(path, &patterns), "{}", path);
+        }
+        assert!(!is_excluded("anything", &[]));
+    }
+
+    #[test]
+    fn copy_without_excluded_files() {
+        let source = tempfile::TempDir::new().unwrap();
+        let target = tempfile::TempDir::new().unwrap();
+        for (path, content) in [
+            ("manifest.json", "{}"),
+            ("textures/stone.png", "png"),
+            ("textures/stone.psd", "psd"),
+            ("raw/only.psd", "psd"),
+            (".git/config", "git"),
+        ] {
+            let path = source.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        fs::create_dir_all(source.path().join("empty")).unwrap();
+
+        let target = target.path().join("pack");
+        let excluded = copy_pack(source.path(), &target, &patterns(&["**/*.psd", ".git"])).unwrap();
+        assert_eq!(excluded, 3);
+
+        let mut copied = list_relative_paths(&target.as_os_str().to_os_string())
+            .unwrap()
+            .iter()
+            .map(|path| path.to_string_lossy().replace(MAIN_SEPARATOR, "/"))
+            .collect::<Vec<_>>();
+        copied.sort();
+        // Folders with only excluded files and empty ones are left out
+        assert_eq!(copied, ["manifest.json", "textures/", "textures/stone.png"]);
+        assert_eq!(
+            fs::read_to_string(target.join("textures/stone.png")).unwrap(),
+            "png"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Project configuration, kotik.toml in the pack repository.
+// Named profiles keep everything needed to reproduce a build, e.g.:
+//
+// default_profile = "dev"
+//
+// [profiles.release]
+// source = "pack"
+// output = "dist"
+// targets = ["mcpack"]
+// key = { env = "KOTIK_KEY" }
+// exclude = ["**/*.psd", "**/.git"]
+// minify = true
+
+pub const CONFIG_FILE: &str = "kotik.toml";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    // Pack folder, paths are relative to kotik.toml
+    pub source: PathBuf,
+    // Folder for build artifacts, build/<profile> by default
+    pub output: Option<PathBuf>,
+    // Base name of artifacts, source folder name by default
+    pub name: Option<String>,
+    #[serde(default = "default_targets")]
+    pub targets: Vec<ExportTarget>,
+    #[serde(default)]
+    pub key: KeySource,
+    // Glob patterns of paths relative to source. Matching folder
+    // is excluded with everything inside
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_lint")]
+    pub lint: bool,
+    #[serde(default)]
+    pub minify: bool,
+    #[serde(default)]
+    pub strict_json: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportTarget {
+    // Encrypted pack folder
+    Folder,
+    Mcpack,
+    Zip,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    // New key for every build
+    #[default]
+    Random,
+    // Name of environment variable
+    Env(String),
+    // Path to file with the key, relative to kotik.toml
+    File(PathBuf),
+    Value(String),
+}
+
+fn default_targets() -> Vec<ExportTarget> {
+    vec![ExportTarget::Folder]
+}
+
+fn default_lint() -> bool {
+    true
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    NotFound(PathBuf),
+    ReadError(std::io::Error),
+    ParseError(toml::de::Error),
+    NoProfiles,
+    // Requested name and available profiles
+    UnknownProfile(String, Vec<String>),
+    // Profile doesn't say which one to use, and there are several
+    AmbiguousProfile(Vec<String>),
+    InvalidExcludePattern(String, glob::PatternError),
+    KeyEnvNotSet(String),
+    KeyFileError(PathBuf, std::io::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::NotFound(path) => {
+                write!(f, "Project config {} not found", path.display())
+            }
+            ConfigError::ReadError(_) => write!(f, "Can't read project config"),
+            ConfigError::ParseError(_) => write!(f, "Can't parse project config"),
+            ConfigError::NoProfiles => write!(f, "Project config has no profiles"),
+            ConfigError::UnknownProfile(name, available) => write!(
+                f,
+                "Unknown profile '{}'. Available profiles: {}",
+                name,
+                available.join(", ")
+            ),
+            ConfigError::AmbiguousProfile(available) => write!(
+                f,
+                "Specify profile with --profile or set default_profile. Available profiles: {}",
+                available.join(", ")
+            ),
+            ConfigError::InvalidExcludePattern(pattern, _) => {
+                write!(f, "Invalid exclude pattern '{}'", pattern)
+            }
+            ConfigError::KeyEnvNotSet(name) => {
+                write!(f, "Environment variable {} with the key is not set", name)
+            }
+            ConfigError::KeyFileError(path, _) => {
+                write!(f, "Can't read key file {}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::ReadError(e) => Some(e),
+            ConfigError::ParseError(e) => Some(e),
+            ConfigError::InvalidExcludePattern(_, e) => Some(e),
+            ConfigError::KeyFileError(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub fn read_config(path: &Path) -> Result<ProjectConfig, ConfigError> {
+    if !path.is_file() {
+        return Err(ConfigError::NotFound(path.to_path_buf()));
+    }
+
+    let content = fs::read_to_string(path).map_err(ConfigError::ReadError)?;
+    toml::from_str(&content).map_err(ConfigError::ParseError)
+}
+
+impl ProjectConfig {
+    // Without name default_profile is used, or the only one profile
+    pub fn profile(&self, name: Option<&str>) -> Result<(String, &Profile), ConfigError> {
+        let available = || self.profiles.keys().cloned().collect::<Vec<_>>();
+
+        let name = match name.or(self.default_profile.as_deref()) {
+            Some(name) => name,
+            None => match self.profiles.len() {
+                0 => return Err(ConfigError::NoProfiles),
+                1 => self.profiles.keys().next().unwrap(),
+                _ => return Err(ConfigError::AmbiguousProfile(available())),
+            },
+        };
+
+        self.profiles
+            .get_key_value(name)
+            .map(|(name, profile)| (name.clone(), profile))
+            .ok_or_else(|| ConfigError::UnknownProfile(name.to_owned(), available()))
+    }
+}
+
+impl KeySource {
+    // Base directory is the folder of kotik.toml
+    pub fn resolve(&self, base: &Path) -> Result<Option<String>, ConfigError> {
+        match self {
+            KeySource::Random => Ok(None),
+            KeySource::Env(name) => env::var(name)
+                .map(|key| Some(key.trim().to_owned()))
+                .map_err(|_| ConfigError::KeyEnvNotSet(name.clone())),
+            KeySource::File(path) => {
+                let path = base.join(path);
+                fs::read_to_string(&path)
+                    .map(|key| Some(key.trim().to_owned()))
+                    .map_err(|e| ConfigError::KeyFileError(path, e))
+            }
+            KeySource::Value(key) => Ok(Some(key.clone())),
+        }
+    }
+}
+
+impl Profile {
+    pub fn exclude_patterns(&self) -> Result<Vec<Pattern>, ConfigError> {
+        self.exclude
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .map_err(|e| ConfigError::InvalidExcludePattern(pattern.clone(), e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(text: &str) -> ProjectConfig {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn profile_resolution() {
+        let single = config("[profiles.dev]\nsource = \"pack\"");
+        assert_eq!(single.profile(None).unwrap().0, "dev");
+        assert!(matches!(
+            single.profile(Some("release")),
+            Err(ConfigError::UnknownProfile(name, available)) if name == "release" && available == ["dev"]
+        ));
+
+        let several = config(
+            "[profiles.dev]\nsource = \"pack\"\n[profiles.release]\nsource = \"pack\"\nminify = true",
+        );
+        assert!(matches!(
+            several.profile(None),
+            Err(ConfigError::AmbiguousProfile(available)) if available == ["dev", "release"]
+        ));
+        let (name, profile) = several.profile(Some("release")).unwrap();
+        assert_eq!(name, "release");
+        assert!(profile.minify);
+        assert!(profile.lint);
+        assert_eq!(profile.targets, [ExportTarget::Folder]);
+
+        let default = config(
+            "default_profile = \"release\"\n[profiles.dev]\nsource = \"pack\"\n[profiles.release]\nsource = \"pack\"",
+        );
+        assert_eq!(default.profile(None).unwrap().0, "release");
+        // Explicit name wins over default_profile
+        assert_eq!(default.profile(Some("dev")).unwrap().0, "dev");
+
+        assert!(matches!(
+            config("").profile(None),
+            Err(ConfigError::NoProfiles)
+        ));
+        assert!(
+            toml::from_str::<ProjectConfig>("[profiles.dev]\nsource = \"pack\"\nunknown = 1")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn key_sources() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fs::write(temp.path().join("pack.key"), "file key\n").unwrap();
+
+        let key = |text: &str| {
+            config(&format!(
+                "[profiles.dev]\nsource = \"pack\"\nkey = {}",
+                text
+            ))
+            .profiles["dev"]
+                .key
+                .resolve(temp.path())
+        };
+
+        assert!(matches!(
+            config("[profiles.dev]\nsource = \"pack\"").profiles["dev"].key,
+            KeySource::Random
+        ));
+        assert_eq!(key("\"random\"").unwrap(), None);
+        assert_eq!(
+            key("{ value = \"value key\" }").unwrap().unwrap(),
+            "value key"
+        );
+        assert_eq!(key("{ file = \"pack.key\" }").unwrap().unwrap(), "file key");
+        assert!(matches!(
+            key("{ file = \"missing.key\" }"),
+            Err(ConfigError::KeyFileError(path, _)) if path == temp.path().join("missing.key")
+        ));
+
+        env::set_var("KOTIK_TEST_CONFIG_KEY", " env key ");
+        assert_eq!(
+            key("{ env = \"KOTIK_TEST_CONFIG_KEY\" }").unwrap().unwrap(),
+            "env key"
+        );
+        assert!(matches!(
+            key("{ env = \"KOTIK_TEST_CONFIG_KEY_UNSET\" }"),
+            Err(ConfigError::KeyEnvNotSet(name)) if name == "KOTIK_TEST_CONFIG_KEY_UNSET"
+        ));
+    }
+
+    #[test]
+    fn invalid_exclude_pattern() {
+        let config = config("[profiles.dev]\nsource = \"pack\"\nexclude = [\"**/*.psd\", \"[\"]");
+        assert!(matches!(
+            config.profiles["dev"].exclude_patterns(),
+            Err(ConfigError::InvalidExcludePattern(pattern, _)) if pattern == "["
+        ));
+    }
+}
@@ -1,7 +1,9 @@
+use crate::packs::storage::PackStorage;
 use crate::utils::cipher::generate_random_key;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 use std::io::{Error, ErrorKind, Write};
-use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ContentsRootItem {
@@ -16,6 +18,46 @@ pub struct ContentsRoot {
     pub content: Vec<ContentsRootItem>,
 }
 
+// Magic number of contents.json generated by Kotik and Bedrock tools
+pub const CONTENTS_MAGIC: u32 = 0x9BCFB9FC;
+pub const CONTENTS_HEADER_SIZE: usize = 0x100;
+
+#[derive(Serialize, Debug)]
+pub struct ContentsHeader {
+    pub version: i32,
+    pub magic: u32,
+    pub uuid: String,
+}
+
+// Reverse of generate_contents_header. It doesn't check magic since
+// marketplace packs have different one
+pub fn parse_contents_header(bytes: &[u8]) -> std::io::Result<ContentsHeader> {
+    if bytes.len() < CONTENTS_HEADER_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "contents.json is shorter than its 256 bytes header",
+        ));
+    }
+
+    let version = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let magic = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+    // Length byte goes after 16 bytes, UUID can't go out of the header
+    let uuid_length = bytes[0x10] as usize;
+    let uuid = bytes
+        .get(0x11..0x11 + uuid_length)
+        .filter(|_| 0x11 + uuid_length <= CONTENTS_HEADER_SIZE)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "UUID doesn't fit in the header"))?;
+    let uuid = String::from_utf8(uuid.to_vec())
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "UUID is not valid UTF-8"))?;
+
+    Ok(ContentsHeader {
+        version,
+        magic,
+        uuid,
+    })
+}
+
 // Generating contents.json header
 pub fn generate_contents_header(uuid: &str) -> std::io::Result<Vec<u8>> {
     // Header is always 256 bytes
@@ -27,14 +69,15 @@ pub fn generate_contents_header(uuid: &str) -> std::io::Result<Vec<u8>> {
     // NOTICE: for some reason marketplace packs have different magic number
     // Marketplace bytes: [239, 252, 191, 189, 239, 191, 189, 207]
     // You can get this magic number without decryption
-    buffer.write_all(&0x9BCFB9FCu32.to_le_bytes())?;
+    buffer.write_all(&CONTENTS_MAGIC.to_le_bytes())?;
     buffer.write_all(&0i64.to_le_bytes())?;
 
     let uuid_bytes = uuid.as_bytes();
-    if uuid_bytes.len() > 255 {
+    // Whatever is left of the header after 16 bytes and length byte
+    if uuid_bytes.len() > 0xEF {
         return Err(Error::new(
             ErrorKind::InvalidInput,
-            "UUID exceeds maximum length of 255 bytes",
+            "UUID exceeds maximum length of 239 bytes",
         ));
     }
 
@@ -66,7 +109,7 @@ pub fn generate_contents_root(relative_paths: &[PathBuf]) -> Vec<ContentsRootIte
 pub const DONT_ENCRYPT: [&str; 4] = ["manifest.json", "contents.json", "pack_icon.png", "texts/"];
 
 // Check if it folder or some of DO_NOT_ENCRYPT files
-fn should_generate_key(path: &Path) -> bool {
+pub(crate) fn should_generate_key(path: &Path) -> bool {
     // string_lossy is cheap conversion, but you always
     // can debunk my code
     let path_as_string = path.to_string_lossy();
@@ -79,3 +122,89 @@ fn should_generate_key(path: &Path) -> bool {
         .iter()
         .any(|pattern| path_as_string.starts_with(pattern))
 }
+
+// Paths of contents.json come from the pack, so decrypted pack from a third party
+// could list something like ../../.bashrc to overwrite files outside of it
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsafePathReason {
+    Absolute,
+    ParentDirectory,
+    // Goes through symlink, that points outside of the pack or nowhere
+    SymlinkEscape,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UnsafePath {
+    pub path: String,
+    pub reason: UnsafePathReason,
+}
+
+impl Display for UnsafePath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.reason {
+            UnsafePathReason::Absolute => "absolute path",
+            UnsafePathReason::ParentDirectory => "goes above the pack folder with ..",
+            UnsafePathReason::SymlinkEscape => "goes through symlink leading outside of the pack",
+        };
+        write!(f, "{}: {}", self.path, reason)
+    }
+}
+
+// Root must be canonical. Without root only lexical checks are done
+fn check_entry_path(root: Option<&Path>, path: &str) -> Result<(), UnsafePathReason> {
+    let mut current = root.map(Path::to_path_buf).unwrap_or_default();
+    // Files that don't exist yet can't be symlinks, but the rest
+    // of the path still has to be checked for ..
+    let mut exists = root.is_some();
+
+    for component in Path::new(path).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return Err(UnsafePathReason::Absolute),
+            Component::ParentDir => return Err(UnsafePathReason::ParentDirectory),
+            Component::CurDir => continue,
+            Component::Normal(name) => current.push(name),
+        }
+
+        if !exists {
+            continue;
+        }
+        match current.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                if !current
+                    .canonicalize()
+                    .is_ok_and(|target| root.is_some_and(|root| target.starts_with(root)))
+                {
+                    return Err(UnsafePathReason::SymlinkEscape);
+                }
+            }
+            Ok(_) => {}
+            Err(_) => exists = false,
+        }
+    }
+
+    Ok(())
+}
+
+// Entries of contents.json, that would be read or written outside of the pack root.
+// Symlinks are checked only for storage on disk
+pub fn find_unsafe_paths(
+    storage: &dyn PackStorage,
+    items: &[ContentsRootItem],
+) -> std::io::Result<Vec<UnsafePath>> {
+    let root = storage
+        .local_path()
+        .map(|root| root.canonicalize())
+        .transpose()?;
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            check_entry_path(root.as_deref(), &item.path)
+                .err()
+                .map(|reason| UnsafePath {
+                    path: item.path.clone(),
+                    reason,
+                })
+        })
+        .collect())
+}
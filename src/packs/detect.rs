@@ -0,0 +1,97 @@
+use crate::packs::contents::{parse_contents_header, should_generate_key, CONTENTS_MAGIC};
+use crate::packs::storage::PackStorage;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+// Encrypting already encrypted pack makes it unrecoverable without both keys,
+// so encrypt checks the pack first. Header of contents.json is a sure sign,
+// otherwise some files are checked for being readable
+
+// Enough to tell, and big packs are checked fast
+const SAMPLE_SIZE: usize = 64;
+const TEXT_EXTENSIONS: [&str; 4] = ["json", "material", "lang", "mcfunction"];
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum EncryptionEvidence {
+    ContentsHeader { uuid: String },
+    // Files that don't look like their format
+    EncryptedFiles { checked: usize, files: Vec<String> },
+}
+
+impl Display for EncryptionEvidence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncryptionEvidence::ContentsHeader { uuid } => {
+                write!(f, "contents.json has encrypted header of pack {}", uuid)
+            }
+            EncryptionEvidence::EncryptedFiles { checked, files } => write!(
+                f,
+                "{} of {} checked files look encrypted, e.g. {}",
+                files.len(),
+                checked,
+                files[0]
+            ),
+        }
+    }
+}
+
+// None if file format is unknown
+fn looks_encrypted(storage: &dyn PackStorage, path: &Path) -> Option<bool> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    if extension == "png" {
+        let signature = storage.read_prefix(path, PNG_SIGNATURE.len()).ok()?;
+        // Too short for png anyway
+        return Some(signature.len() == PNG_SIGNATURE.len() && signature != PNG_SIGNATURE);
+    }
+
+    if TEXT_EXTENSIONS.contains(&extension.as_str()) {
+        // Ciphertext is almost never valid UTF-8
+        return Some(String::from_utf8(storage.read(path).ok()?).is_err());
+    }
+
+    None
+}
+
+pub fn detect_encryption(
+    storage: &dyn PackStorage,
+    relative_paths: &[PathBuf],
+) -> Option<EncryptionEvidence> {
+    let header = storage
+        .read(Path::new("contents.json"))
+        .ok()
+        .and_then(|bytes| parse_contents_header(&bytes).ok())
+        .filter(|header| header.magic == CONTENTS_MAGIC);
+    if let Some(header) = header {
+        return Some(EncryptionEvidence::ContentsHeader { uuid: header.uuid });
+    }
+
+    let mut checked = 0;
+    let mut files = Vec::new();
+    for rel_path in relative_paths
+        .iter()
+        .filter(|rel_path| should_generate_key(rel_path))
+    {
+        let Some(encrypted) = looks_encrypted(storage, rel_path) else {
+            continue;
+        };
+
+        checked += 1;
+        if encrypted {
+            files.push(rel_path.to_string_lossy().replace(MAIN_SEPARATOR, "/"));
+        }
+        if checked == SAMPLE_SIZE {
+            break;
+        }
+    }
+
+    // A few broken files don't make the pack encrypted
+    if !files.is_empty() && files.len() * 2 > checked {
+        Some(EncryptionEvidence::EncryptedFiles { checked, files })
+    } else {
+        None
+    }
+}
@@ -1,79 +1,160 @@
-use crate::packs::contents::ContentsRoot;
-use crate::packs::pack_encryption::{parallel_processing, write_file, PackEncryptionError};
+use crate::packs::contents::{
+    find_unsafe_paths, parse_contents_header, ContentsHeader, ContentsRoot, CONTENTS_HEADER_SIZE,
+};
+use crate::packs::pack_encryption::{
+    parallel_processing, FileError, FileOperation, PackEncryptionError, ProcessingOptions,
+};
+use crate::packs::progress::{ProgressCallback, ProgressStage};
+use crate::packs::storage::{DirectoryStorage, PackStorage};
 use crate::utils::cipher::aes256_cfb8_decrypt;
-use crate::utils::cli::get_choice;
+use serde::Serialize;
 use std::ffi::OsString;
-use std::fs::read;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
-// This function can be represented as stages:
-// 1. Parsing content.json
-// 2. Decrypting all files
-pub fn decrypt(key: &str, target_path: OsString) -> Result<(), PackEncryptionError> {
-    // Asking user again
-    if !get_choice(
-        format!("Are you sure you want to decrypt the data on the following path: {:#?}? Files will be rewrote permanently.",
-            target_path
-        )
-    ) {
-        return Err(PackEncryptionError::Abort);
-    }
+#[derive(Debug, Default, Clone)]
+pub struct DecryptOptions {
+    // Worker threads, fail fast and cancellation of decrypt stage
+    pub processing: ProcessingOptions,
+    // Receives progress of decrypt stage
+    pub progress: Option<ProgressCallback>,
+}
 
-    println!("Parsing contents.json file...");
+#[derive(Debug, Serialize)]
+pub struct DecryptReport {
+    // UUID from contents.json header
+    pub uuid: String,
+    // Files and folders listed in contents.json
+    pub entries: usize,
+    pub decrypted_files: usize,
+    pub decrypted_bytes: u64,
+}
 
-    let contents_file_path = Path::new(&target_path).join("contents.json");
-    let contents_file_content =
-        read(&contents_file_path).map_err(PackEncryptionError::FileSystemError)?;
+// Returns header, parsed root and decrypted json of contents.json
+pub fn decrypt_contents(
+    key: &str,
+    contents_file_content: &[u8],
+) -> Result<(ContentsHeader, ContentsRoot, Vec<u8>), PackEncryptionError> {
+    let header = parse_contents_header(contents_file_content).map_err(|e| {
+        PackEncryptionError::FileError(FileError::new("contents.json", FileOperation::Read, e))
+    })?;
 
     let decrypted_content = aes256_cfb8_decrypt(
         key,
         // Removing header to get correct json
-        contents_file_content[0x100..].to_vec(),
+        contents_file_content[CONTENTS_HEADER_SIZE..].to_vec(),
     )
     .map_err(PackEncryptionError::CipherError)?;
 
-    // We will also write decrypted contents.json for more understanding
-    write_file(&decrypted_content, &contents_file_path)
-        .map_err(PackEncryptionError::FileSystemError)?;
-
     // Deserializing json
     let contents_root: ContentsRoot = serde_json::from_str(
-        &String::from_utf8(decrypted_content)
+        &String::from_utf8(decrypted_content.clone())
             .map_err(PackEncryptionError::ContentsDecodingError)?,
     )
     .map_err(PackEncryptionError::JsonError)?;
 
+    Ok((header, contents_root, decrypted_content))
+}
+
+// This function can be represented as stages:
+// 1. Parsing content.json and checking that all its paths stay inside the pack
+// 2. Decrypting all files
+pub fn decrypt(
+    key: &str,
+    target_path: OsString,
+    options: &DecryptOptions,
+) -> Result<DecryptReport, PackEncryptionError> {
+    decrypt_storage(key, &DirectoryStorage::new(target_path), options)
+}
+
+// Same as decrypt, for pack in any storage
+pub fn decrypt_storage(
+    key: &str,
+    storage: &dyn PackStorage,
+    options: &DecryptOptions,
+) -> Result<DecryptReport, PackEncryptionError> {
+    let contents_file_path = Path::new("contents.json");
+    let contents_file_content = storage.read(contents_file_path).map_err(|e| {
+        PackEncryptionError::FileError(FileError::new("contents.json", FileOperation::Read, e))
+    })?;
+
+    let (header, contents_root, decrypted_content) = decrypt_contents(key, &contents_file_content)?;
+
+    // Nothing is written, if any path leads outside of the pack
+    let unsafe_paths = find_unsafe_paths(storage, &contents_root.content)
+        .map_err(PackEncryptionError::FileSystemError)?;
+    if !unsafe_paths.is_empty() {
+        return Err(PackEncryptionError::UnsafePaths(unsafe_paths));
+    }
+
+    // We will also write decrypted contents.json for more understanding
+    storage
+        .write(contents_file_path, &decrypted_content)
+        .map_err(|e| {
+            PackEncryptionError::FileError(FileError::new("contents.json", FileOperation::Write, e))
+        })?;
+
+    let mut report = DecryptReport {
+        uuid: header.uuid,
+        entries: contents_root.content.len(),
+        decrypted_files: 0,
+        decrypted_bytes: 0,
+    };
+
     // Decrypting
-    parallel_processing(contents_root.content, move |item| {
-        let path = &item.path;
-        let full_path = Path::new(&target_path).join(path);
-
-        // We need this system call since if contents.json isn't
-        // generated with Kotik, we can't verify that
-        // all it paths is valid
-        if full_path.is_dir() {
-            return Ok(());
-        }
-
-        let key = match &item.key {
-            Some(v) => v,
-            None => return Ok(()),
-        };
-
-        let encrypted_file_content =
-            read(&full_path).map_err(|e| format!("Can't read file: {}", e))?;
-
-        let decrypted_file_content = aes256_cfb8_decrypt(key, encrypted_file_content)
-            .map_err(|e| format!("Can't decrypt file: {:#?}", e))?;
-
-        // Writing decrypted file
-        write_file(&decrypted_file_content, &full_path)
-            .map_err(|e| format!("Can't write file: {}", e))?;
-
-        println!("Decrypted: {} with key {}", path, key);
-
-        Ok(())
-    })
-    .map_err(PackEncryptionError::ProcessingError)?;
-    Ok(())
+    let decrypted_files = AtomicUsize::new(0);
+    let decrypted_bytes = AtomicU64::new(0);
+
+    // Files without key weren't encrypted
+    let tasks = contents_root
+        .content
+        .into_iter()
+        .filter(|item| item.key.is_some())
+        .collect::<Vec<_>>();
+    let progress = options
+        .progress
+        .clone()
+        .map(|callback| (ProgressStage::Decrypt, callback));
+
+    parallel_processing(
+        tasks,
+        |item| {
+            let path = &item.path;
+
+            // We need this check since if contents.json isn't
+            // generated with Kotik, we can't verify that
+            // all it paths is valid
+            if storage.is_dir(Path::new(path)) {
+                return Ok(());
+            }
+
+            let key = match &item.key {
+                Some(v) => v,
+                None => return Ok(()),
+            };
+
+            let encrypted_file_content = storage
+                .read(Path::new(path))
+                .map_err(|e| FileError::new(path, FileOperation::Read, e))?;
+            decrypted_bytes.fetch_add(encrypted_file_content.len() as u64, Ordering::Relaxed);
+
+            let decrypted_file_content = aes256_cfb8_decrypt(key, encrypted_file_content)
+                .map_err(|e| FileError::new(path, FileOperation::Decrypt, e))?;
+
+            // Writing decrypted file
+            storage
+                .write(Path::new(path), &decrypted_file_content)
+                .map_err(|e| FileError::new(path, FileOperation::Write, e))?;
+
+            decrypted_files.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        },
+        &options.processing,
+        progress,
+    )
+    .map_err(PackEncryptionError::from)?;
+
+    report.decrypted_files = decrypted_files.load(Ordering::Relaxed);
+    report.decrypted_bytes = decrypted_bytes.load(Ordering::Relaxed);
+    Ok(report)
 }
@@ -1,87 +1,279 @@
 use crate::packs::contents::{generate_contents_header, generate_contents_root, ContentsRoot};
-use crate::packs::manifest::get_uuid_from_manifest;
+use crate::packs::detect::detect_encryption;
+use crate::packs::lint::{lint_storage, LintIssue, LintSeverity};
+use crate::packs::manifest::read_storage_manifest;
+use crate::packs::minify::{minify_json_files, MinifyStats};
 use crate::packs::pack_encryption::{
-    list_relative_paths, parallel_processing, write_file, PackEncryptionError,
+    parallel_processing, FileError, FileOperation, PackEncryptionError, PartialEncryption,
+    ProcessingOptions,
 };
-use crate::utils::cipher::aes256_cbf8_encrypt;
-use crate::utils::cli::get_choice;
+use crate::packs::progress::{ProgressCallback, ProgressStage};
+use crate::packs::storage::{DirectoryStorage, PackStorage};
+use crate::packs::subpacks::{collect_subpack_stats, validate_subpack_folders, SubpackStats};
+use crate::utils::cipher::{aes256_cbf8_encrypt, CipherError};
+use serde::Serialize;
 use serde_json::json;
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::fs::read;
 use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
-// This function can be represented as stages:
-// 1. Collecting uuid and relative paths
-// 2. Generating and writing contents.json file
-// 3. Encrypting files
-pub fn encrypt(key: &str, target_path: OsString) -> Result<(), PackEncryptionError> {
-    println!("Collecting data...");
-
-    let uuid =
-        get_uuid_from_manifest(&target_path).map_err(PackEncryptionError::DataCollectionError)?;
-
-    let relative_paths =
-        list_relative_paths(&target_path).map_err(PackEncryptionError::FileSystemError)?;
-
-    // Ask user once again
-    if !get_choice(
-        format!("Are you sure you want to encrypt the data on the following path: {:#?}? Files will be rewrote permanently.",
-                target_path)
-    ) {
-        return Err(PackEncryptionError::Abort);
-    }
-
-    // Start with generating contents.json file
-    let mut content_file_as_bytes =
-        generate_contents_header(&uuid).map_err(PackEncryptionError::ContentsGeneratingError)?;
+#[derive(Debug, Default, Clone)]
+pub struct EncryptOptions {
+    // Run pack linter before encryption, lint errors abort it
+    pub lint: bool,
+    // Strip comments and whitespaces from .json and .material files, except manifest.json
+    pub minify: bool,
+    // Fail on json that can't be minified instead of leaving it as is
+    pub strict_json: bool,
+    // Encrypt even if pack looks already encrypted
+    pub force: bool,
+    // Worker threads, fail fast and cancellation of minify and encrypt stages
+    pub processing: ProcessingOptions,
+    // Receives progress of minify and encrypt stages
+    pub progress: Option<ProgressCallback>,
+}
 
-    let content = generate_contents_root(&relative_paths);
+#[derive(Debug, Serialize)]
+pub struct EncryptReport {
+    pub uuid: String,
+    // Files and folders listed in contents.json
+    pub files: usize,
+    pub directories: usize,
+    pub encrypted_files: usize,
+    pub encrypted_bytes: u64,
+    pub lint_warnings: Vec<LintIssue>,
+    pub minify: Option<MinifyStats>,
+    pub subpacks: Vec<SubpackStats>,
+}
 
-    let root = ContentsRoot {
-        version: 1,
-        content,
-    };
+// Header with UUID followed by encrypted json of the root
+pub fn generate_contents_file(
+    key: &str,
+    uuid: &str,
+    root: &ContentsRoot,
+) -> Result<Vec<u8>, PackEncryptionError> {
+    let mut content_file_as_bytes =
+        generate_contents_header(uuid).map_err(PackEncryptionError::ContentsGeneratingError)?;
 
     let root_as_json_in_bytes = json!(root).to_string().as_bytes().to_vec();
 
-    // Encrypting contents.json file root and writing it to the target path
+    // Encrypting contents.json file root
     let encrypted_root = aes256_cbf8_encrypt(key, root_as_json_in_bytes)
         .map_err(PackEncryptionError::CipherError)?;
 
     content_file_as_bytes
         .write_all(&encrypted_root)
         .map_err(PackEncryptionError::ContentsGeneratingError)?;
+    Ok(content_file_as_bytes)
+}
 
-    let contents_file_path = Path::new(&target_path).join("contents.json");
+fn write_contents_file(
+    key: &str,
+    storage: &dyn PackStorage,
+    uuid: &str,
+    root: &ContentsRoot,
+) -> Result<(), PackEncryptionError> {
+    let content_file_as_bytes = generate_contents_file(key, uuid, root)?;
+    storage
+        .write(Path::new("contents.json"), &content_file_as_bytes)
+        .map_err(|e| {
+            PackEncryptionError::FileError(FileError::new("contents.json", FileOperation::Write, e))
+        })
+}
+
+// This function can be represented as stages:
+// 1. Collecting and validating manifest and subpacks, collecting relative paths,
+//    checking that pack isn't encrypted yet
+// 2. Linting pack (optional)
+// 3. Minifying json files (optional)
+// 4. Writing contents.json file with keys of all files
+// 5. Encrypting files. If it stops in the middle, contents.json is written
+//    again with keys of encrypted files only
+pub fn encrypt(
+    key: &str,
+    target_path: OsString,
+    options: &EncryptOptions,
+) -> Result<EncryptReport, PackEncryptionError> {
+    encrypt_storage(key, &DirectoryStorage::new(target_path), options)
+}
+
+// Same as encrypt, for pack in any storage
+pub fn encrypt_storage(
+    key: &str,
+    storage: &dyn PackStorage,
+    options: &EncryptOptions,
+) -> Result<EncryptReport, PackEncryptionError> {
+    // Checked before anything is changed, since contents.json can't be written without it
+    if key.len() != 32 {
+        return Err(PackEncryptionError::CipherError(
+            CipherError::InvalidKeyLength(key.len()),
+        ));
+    }
 
-    write_file(&content_file_as_bytes, &contents_file_path)
-        .map_err(PackEncryptionError::FileSystemError)?;
+    let manifest = read_storage_manifest(storage).map_err(PackEncryptionError::ManifestError)?;
+    manifest
+        .validate()
+        .map_err(PackEncryptionError::ManifestError)?;
+    validate_subpack_folders(storage, &manifest)
+        .map_err(PackEncryptionError::DataCollectionError)?;
+
+    // Decrypted contents.json of the previous encryption is replaced, not listed
+    let relative_paths = storage
+        .list_entries()
+        .map_err(PackEncryptionError::FileSystemError)?
+        .into_iter()
+        .filter(|path| path != Path::new("contents.json"))
+        .collect::<Vec<_>>();
+
+    if !options.force {
+        if let Some(evidence) = detect_encryption(storage, &relative_paths) {
+            return Err(PackEncryptionError::AlreadyEncrypted(evidence));
+        }
+    }
+
+    let mut lint_warnings = Vec::new();
+    if options.lint {
+        let errors;
+        (errors, lint_warnings) = lint_storage(storage)
+            .map_err(PackEncryptionError::FileSystemError)?
+            .into_iter()
+            .partition(|issue: &LintIssue| issue.severity == LintSeverity::Error);
+
+        if !errors.is_empty() {
+            return Err(PackEncryptionError::LintError(errors));
+        }
+    }
+
+    let mut minify = None;
+    if options.minify {
+        minify = Some(
+            minify_json_files(
+                storage,
+                &relative_paths,
+                options.strict_json,
+                &options.processing,
+                options.progress.clone(),
+            )
+            .map_err(PackEncryptionError::from)?,
+        );
+    }
+
+    // Start with generating contents.json file
+    let content = generate_contents_root(&relative_paths);
+    let subpacks = collect_subpack_stats(storage, &manifest, &content);
+
+    let directories = content
+        .iter()
+        .filter(|item| item.path.ends_with('/'))
+        .count();
+    let encrypted_files = content.iter().filter(|item| item.key.is_some()).count();
+    let mut report = EncryptReport {
+        uuid: manifest.header.uuid,
+        files: content.len() - directories,
+        directories,
+        encrypted_files,
+        encrypted_bytes: 0,
+        lint_warnings,
+        minify,
+        subpacks,
+    };
+
+    let mut root = ContentsRoot {
+        version: 1,
+        content,
+    };
+
+    // contents.json with all keys is written before any file is encrypted,
+    // so keys aren't lost even if the process is killed in the middle
+    let contents_path = Path::new("contents.json");
+    let previous_contents = storage.read(contents_path).ok();
+    write_contents_file(key, storage, &report.uuid, &root)?;
 
     // Encrypting files
-    parallel_processing(root.content, move |item| {
-        let path = &item.path;
-        let full_path = Path::new(&target_path).join(path);
-
-        let key = match &item.key {
-            Some(key) => key,
-            None => return Ok(()),
-        };
-
-        // We need to read it after key validation since folders don't have
-        // key, and we can escape calling system check
-        let file_content =
-            read(&full_path).map_err(|e| format!("Can't read file {}: {}", &path, e))?;
-
-        let encrypted_file_content = aes256_cbf8_encrypt(key, file_content)
-            .map_err(|e| format!("Can't encrypt: {:#?}", e))?;
-
-        write_file(&encrypted_file_content, &full_path)
-            .map_err(|e| format!("Can't write file: {}", e))?;
-
-        println!("Encrypted: {}", path);
-        Ok(())
-    })
-    .map_err(PackEncryptionError::ProcessingError)?;
-    Ok(())
+    let encrypted_bytes = AtomicU64::new(0);
+    let encrypted_paths = Mutex::new(HashSet::new());
+
+    // Folders and files without key are left as is, so they aren't tasks
+    let tasks = root
+        .content
+        .iter()
+        .filter(|item| item.key.is_some())
+        .collect::<Vec<_>>();
+    let progress = options
+        .progress
+        .clone()
+        .map(|callback| (ProgressStage::Encrypt, callback));
+
+    let result = parallel_processing(
+        tasks,
+        |item| {
+            let path = &item.path;
+
+            let key = match &item.key {
+                Some(key) => key,
+                None => return Ok(()),
+            };
+
+            // We need to read it after key validation since folders don't have
+            // key, and we can escape calling system check
+            let file_content = storage
+                .read(Path::new(path))
+                .map_err(|e| FileError::new(path, FileOperation::Read, e))?;
+            encrypted_bytes.fetch_add(file_content.len() as u64, Ordering::Relaxed);
+
+            let encrypted_file_content = aes256_cbf8_encrypt(key, file_content)
+                .map_err(|e| FileError::new(path, FileOperation::Encrypt, e))?;
+
+            storage
+                .write(Path::new(path), &encrypted_file_content)
+                .map_err(|e| FileError::new(path, FileOperation::Write, e))?;
+
+            encrypted_paths.lock().unwrap().insert(path.clone());
+            Ok(())
+        },
+        &options.processing,
+        progress,
+    )
+    .map_err(PackEncryptionError::from);
+
+    if let Err(e) = result {
+        let encrypted_paths = encrypted_paths.into_inner().unwrap();
+        // No file was encrypted, so the pack is put back as it was
+        if encrypted_paths.is_empty() {
+            match previous_contents {
+                Some(previous_contents) => storage.write(contents_path, &previous_contents),
+                None => storage.remove(contents_path),
+            }
+            .map_err(|e| {
+                PackEncryptionError::FileError(FileError::new(
+                    "contents.json",
+                    FileOperation::Write,
+                    e,
+                ))
+            })?;
+            return Err(e);
+        }
+
+        // Keys of files left as is are dropped, so the pack can still be decrypted
+        let mut unencrypted = Vec::new();
+        for item in &mut root.content {
+            if item.key.is_some() && !encrypted_paths.contains(&item.path) {
+                item.key = None;
+                unencrypted.push(item.path.clone());
+            }
+        }
+        unencrypted.sort();
+
+        write_contents_file(key, storage, &report.uuid, &root)?;
+        return Err(PackEncryptionError::PartiallyEncrypted(PartialEncryption {
+            unencrypted,
+            cause: Box::new(e),
+        }));
+    }
+
+    report.encrypted_bytes = encrypted_bytes.load(Ordering::Relaxed);
+    Ok(report)
 }
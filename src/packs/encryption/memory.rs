@@ -0,0 +1,67 @@
+use crate::packs::contents::{find_unsafe_paths, ContentsRootItem};
+use crate::packs::encryption::decrypt::{decrypt_storage, DecryptOptions};
+use crate::packs::encryption::encrypt::{encrypt_storage, EncryptOptions};
+use crate::packs::manifest::Manifest;
+use crate::packs::pack_encryption::PackEncryptionError;
+use crate::packs::storage::MemoryStorage;
+use std::collections::BTreeMap;
+
+// Packs generated on the fly (e.g. per-player skins) are encrypted without
+// touching disk. Files are keyed by relative paths with '/', like in contents.json
+
+// Callers may build paths with Windows separators, but packs always use '/'
+pub(crate) fn normalize_paths(
+    files: BTreeMap<String, Vec<u8>>,
+) -> Result<BTreeMap<String, Vec<u8>>, PackEncryptionError> {
+    let files = files
+        .into_iter()
+        .map(|(path, bytes)| (path.replace('\\', "/"), bytes))
+        .collect::<BTreeMap<_, _>>();
+
+    let items = files
+        .keys()
+        .map(|path| ContentsRootItem {
+            path: path.clone(),
+            key: None,
+        })
+        .collect::<Vec<_>>();
+    let unsafe_paths = find_unsafe_paths(&MemoryStorage::new(), &items)
+        .map_err(PackEncryptionError::FileSystemError)?;
+    if let Some(unsafe_path) = unsafe_paths.first() {
+        return Err(PackEncryptionError::DataCollectionError(format!(
+            "Invalid pack file path {}",
+            unsafe_path
+        )));
+    }
+    Ok(files)
+}
+
+// Returns encrypted files together with contents.json. Manifest is written
+// as manifest.json, replacing the one in files, if there is any
+pub fn encrypt_in_memory(
+    key: &str,
+    files: BTreeMap<String, Vec<u8>>,
+    manifest: &Manifest,
+    options: &EncryptOptions,
+) -> Result<BTreeMap<String, Vec<u8>>, PackEncryptionError> {
+    let mut files = normalize_paths(files)?;
+    files.insert(
+        String::from("manifest.json"),
+        serde_json::to_vec_pretty(manifest).map_err(PackEncryptionError::JsonError)?,
+    );
+
+    let storage = MemoryStorage::from(files);
+    encrypt_storage(key, &storage, options)?;
+    Ok(storage.into_files())
+}
+
+// Reverse of encrypt_in_memory, contents.json is returned decrypted
+pub fn decrypt_in_memory(
+    key: &str,
+    files: BTreeMap<String, Vec<u8>>,
+    options: &DecryptOptions,
+) -> Result<BTreeMap<String, Vec<u8>>, PackEncryptionError> {
+    let storage = MemoryStorage::from(normalize_paths(files)?);
+    decrypt_storage(key, &storage, options)?;
+    Ok(storage.into_files())
+}
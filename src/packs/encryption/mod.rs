@@ -1,2 +1,5 @@
 pub mod decrypt;
 pub mod encrypt;
+pub mod memory;
+#[cfg(feature = "tokio")]
+pub mod tasks;
@@ -0,0 +1,142 @@
+use crate::packs::encryption::decrypt::{decrypt, DecryptOptions, DecryptReport};
+use crate::packs::encryption::encrypt::{encrypt, EncryptOptions, EncryptReport};
+use crate::packs::encryption::memory::{decrypt_in_memory, encrypt_in_memory};
+use crate::packs::manifest::Manifest;
+use crate::packs::pack_encryption::{panic_message, CancellationToken, PackEncryptionError};
+use crate::packs::progress::{ProgressCallback, ProgressEvent};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::task::{spawn_blocking, JoinHandle};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+// Async front-end for tokio based servers. Encryption itself stays blocking
+// and runs on the blocking thread pool, so async runtime is never blocked.
+// Functions must be called from inside of a tokio runtime
+
+pub type ProgressStream = UnboundedReceiverStream<ProgressEvent>;
+
+// Encryption running in background. Awaiting it gives the result, progress
+// stream ends when encryption is finished. Dropping the task doesn't stop
+// encryption, use cancel for that
+pub struct PackTask<R> {
+    handle: JoinHandle<Result<R, PackEncryptionError>>,
+    progress: ProgressStream,
+    cancellation: CancellationToken,
+}
+
+impl<R: Send + 'static> PackTask<R> {
+    fn spawn(
+        progress: ProgressStream,
+        cancellation: CancellationToken,
+        function: impl FnOnce() -> Result<R, PackEncryptionError> + Send + 'static,
+    ) -> Self {
+        Self {
+            handle: spawn_blocking(function),
+            progress,
+            cancellation,
+        }
+    }
+}
+
+impl<R> PackTask<R> {
+    pub fn progress(&mut self) -> &mut ProgressStream {
+        &mut self.progress
+    }
+
+    // Files that are already being processed are finished, task then
+    // fails with PackEncryptionError::Cancelled
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+}
+
+impl<R> Future for PackTask<R> {
+    type Output = Result<R, PackEncryptionError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle)
+            .poll(cx)
+            .map(|result| match result {
+                Ok(result) => result,
+                Err(e) if e.is_panic() => Err(PackEncryptionError::WorkerPanicked(
+                    panic_message(e.into_panic().as_ref()),
+                    Vec::new(),
+                )),
+                // Runtime is shutting down
+                Err(_) => Err(PackEncryptionError::Abort),
+            })
+    }
+}
+
+// Forwards progress events into stream, callback given by the caller still gets every event
+fn progress_stream(progress: &mut Option<ProgressCallback>) -> ProgressStream {
+    let (sender, receiver) = unbounded_channel();
+    let callback = progress.take();
+    *progress = Some(ProgressCallback::new(move |event| {
+        if let Some(callback) = &callback {
+            callback.emit(event.clone());
+        }
+        // Nobody listens anymore, that's fine
+        let _ = sender.send(event.clone());
+    }));
+    UnboundedReceiverStream::new(receiver)
+}
+
+pub fn spawn_encrypt(
+    key: impl Into<String>,
+    target_path: impl Into<PathBuf>,
+    mut options: EncryptOptions,
+) -> PackTask<EncryptReport> {
+    let (key, target_path) = (key.into(), target_path.into());
+    PackTask::spawn(
+        progress_stream(&mut options.progress),
+        options.processing.cancellation.clone(),
+        move || encrypt(&key, target_path.into_os_string(), &options),
+    )
+}
+
+pub fn spawn_decrypt(
+    key: impl Into<String>,
+    target_path: impl Into<PathBuf>,
+    mut options: DecryptOptions,
+) -> PackTask<DecryptReport> {
+    let (key, target_path) = (key.into(), target_path.into());
+    PackTask::spawn(
+        progress_stream(&mut options.progress),
+        options.processing.cancellation.clone(),
+        move || decrypt(&key, target_path.into_os_string(), &options),
+    )
+}
+
+// Same as encrypt_in_memory
+pub fn spawn_encrypt_in_memory(
+    key: impl Into<String>,
+    files: BTreeMap<String, Vec<u8>>,
+    manifest: Manifest,
+    mut options: EncryptOptions,
+) -> PackTask<BTreeMap<String, Vec<u8>>> {
+    let key = key.into();
+    PackTask::spawn(
+        progress_stream(&mut options.progress),
+        options.processing.cancellation.clone(),
+        move || encrypt_in_memory(&key, files, &manifest, &options),
+    )
+}
+
+// Same as decrypt_in_memory
+pub fn spawn_decrypt_in_memory(
+    key: impl Into<String>,
+    files: BTreeMap<String, Vec<u8>>,
+    mut options: DecryptOptions,
+) -> PackTask<BTreeMap<String, Vec<u8>>> {
+    let key = key.into();
+    PackTask::spawn(
+        progress_stream(&mut options.progress),
+        options.processing.cancellation.clone(),
+        move || decrypt_in_memory(&key, files, &options),
+    )
+}
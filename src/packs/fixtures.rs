@@ -0,0 +1,25 @@
+// Fixtures shared by unit tests, integration tests, benchmarks and fuzz
+// targets. The last three can't see test code of the crate, so they
+// include this file by path
+#![allow(dead_code)]
+
+pub const KEY: &str = "0123456789abcdef0123456789abcdef";
+pub const UUID: &str = "2d4d1a38-7a77-4c8e-9d5e-3b0f6f2a1c11";
+pub const MODULE_UUID: &str = "6b1f0c7e-1f0e-4c5a-8d53-0a7f2f4e9b22";
+
+// Smallest manifest that passes validation, with UUID and MODULE_UUID
+pub const MANIFEST: &str = r#"{
+    "format_version": 2,
+    "header": {
+        "name": "Kotik test pack",
+        "uuid": "2d4d1a38-7a77-4c8e-9d5e-3b0f6f2a1c11",
+        "version": [1, 0, 0]
+    },
+    "modules": [
+        {
+            "type": "resources",
+            "uuid": "6b1f0c7e-1f0e-4c5a-8d53-0a7f2f4e9b22",
+            "version": [1, 0, 0]
+        }
+    ]
+}"#;
@@ -0,0 +1,80 @@
+use crate::packs::contents::{
+    generate_contents_root, parse_contents_header, ContentsHeader, CONTENTS_MAGIC,
+};
+use crate::packs::manifest::{read_manifest, ManifestVersion};
+use crate::packs::pack_encryption::{error_chain, list_relative_paths, PackEncryptionError};
+use crate::packs::storage::DirectoryStorage;
+use crate::packs::subpacks::{collect_subpack_stats, SubpackStats};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs::{metadata, read};
+use std::path::{Path, MAIN_SEPARATOR};
+
+// Inspect doesn't need a key, so it works for both plain and encrypted packs
+
+#[derive(Debug, Serialize)]
+pub struct InspectReport {
+    pub name: Option<String>,
+    pub uuid: Option<String>,
+    pub version: Option<ManifestVersion>,
+    // Why manifest can't be read, if it can't
+    pub manifest_error: Option<String>,
+    pub encrypted: bool,
+    // Header of contents.json, if pack has encrypted one
+    pub contents_header: Option<ContentsHeader>,
+    pub files: usize,
+    pub directories: usize,
+    pub bytes: u64,
+    pub subpacks: Vec<SubpackStats>,
+}
+
+pub fn inspect(target_path: &OsString) -> Result<InspectReport, PackEncryptionError> {
+    let root = Path::new(target_path);
+    let relative_paths =
+        list_relative_paths(target_path).map_err(PackEncryptionError::FileSystemError)?;
+
+    // Decrypted contents.json is plain json, so header won't be valid
+    let contents_header = read(root.join("contents.json"))
+        .ok()
+        .and_then(|bytes| parse_contents_header(&bytes).ok())
+        .filter(|header| header.magic == CONTENTS_MAGIC);
+
+    let mut report = InspectReport {
+        name: None,
+        uuid: None,
+        version: None,
+        manifest_error: None,
+        encrypted: contents_header.is_some(),
+        contents_header,
+        files: 0,
+        directories: 0,
+        bytes: 0,
+        subpacks: Vec::new(),
+    };
+
+    for rel_path in &relative_paths {
+        if rel_path.to_string_lossy().ends_with(MAIN_SEPARATOR) {
+            report.directories += 1;
+        } else {
+            report.files += 1;
+            report.bytes += metadata(root.join(rel_path))
+                .map(|m| m.len())
+                .unwrap_or_default();
+        }
+    }
+
+    match read_manifest(target_path) {
+        Ok(manifest) => {
+            // Keys are not needed here, we only need the listing
+            let content = generate_contents_root(&relative_paths);
+            report.subpacks =
+                collect_subpack_stats(&DirectoryStorage::new(root), &manifest, &content);
+            report.name = Some(manifest.header.name).filter(|name| !name.is_empty());
+            report.uuid = Some(manifest.header.uuid);
+            report.version = manifest.header.version;
+        }
+        Err(e) => report.manifest_error = Some(error_chain(&e)),
+    }
+
+    Ok(report)
+}
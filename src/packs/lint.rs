@@ -0,0 +1,624 @@
+use crate::packs::detect::{detect_encryption, EncryptionEvidence};
+use crate::packs::manifest::{read_storage_manifest, ManifestError};
+use crate::packs::pack_encryption::error_chain;
+use crate::packs::storage::{DirectoryStorage, PackStorage};
+use crate::packs::subpacks::validate_subpack_folders;
+use crate::utils::jsonc::parse_jsonc;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, MAIN_SEPARATOR};
+
+// Pack linter. Once pack is encrypted bugs are much harder to find,
+// so we check everything we can before encryption
+
+// Files that reference textures by path without extension
+const TEXTURE_LISTS: [&str; 2] = [
+    "textures/terrain_texture.json",
+    "textures/item_texture.json",
+];
+const TEXTURE_EXTENSIONS: [&str; 4] = ["png", "tga", "jpg", "jpeg"];
+
+// Textures above this size use a lot of memory on mobile devices
+const MAX_TEXTURE_SIZE: u32 = 2048;
+
+// Everything else is never loaded by Bedrock and only makes pack bigger
+const KNOWN_EXTENSIONS: [&str; 15] = [
+    "json",
+    "material",
+    "png",
+    "tga",
+    "jpg",
+    "jpeg",
+    "lang",
+    "ogg",
+    "fsb",
+    "wav",
+    "js",
+    "mcfunction",
+    "mcstructure",
+    "vertex",
+    "fragment",
+];
+// Hidden files like .DS_Store are caught by the leading dot
+const JUNK_FILES: [&str; 2] = ["Thumbs.db", "desktop.ini"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Warning,
+    // Errors stop encryption
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LintIssue {
+    pub severity: LintSeverity,
+    // Relative path with '/'
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+pub fn lint_pack(target_path: &OsString) -> std::io::Result<Vec<LintIssue>> {
+    lint_storage(&DirectoryStorage::new(target_path))
+}
+
+pub fn lint_storage(storage: &dyn PackStorage) -> std::io::Result<Vec<LintIssue>> {
+    let mut issues = Vec::new();
+    let entries = storage.list_entries()?;
+
+    // Every file of encrypted pack looks broken, so it's reported once instead
+    if let Some(evidence) = detect_encryption(storage, &entries) {
+        let path = match &evidence {
+            EncryptionEvidence::ContentsHeader { .. } => String::from("contents.json"),
+            EncryptionEvidence::EncryptedFiles { files, .. } => files[0].clone(),
+        };
+        issues.push(issue(
+            LintSeverity::Warning,
+            &path,
+            format!(
+                "Pack is encrypted, so it can't be linted: {}. Lint it before encryption",
+                evidence
+            ),
+        ));
+        return Ok(issues);
+    }
+
+    let files = entries
+        .into_iter()
+        .map(|path| path.to_string_lossy().replace(MAIN_SEPARATOR, "/"))
+        // We don't need folders here
+        .filter(|path| !path.ends_with('/'))
+        .collect::<Vec<_>>();
+
+    lint_manifest(storage, &mut issues);
+    lint_case_collisions(&files, &mut issues);
+
+    let files_set = files.iter().map(String::as_str).collect::<HashSet<_>>();
+    let lowercase_files = files
+        .iter()
+        .map(|path| (path.to_lowercase(), path.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    for path in &files {
+        let extension = Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            // Old contents.json is regenerated on encryption
+            "json" | "material" if path != "contents.json" => {
+                let json = lint_json(storage, path, &mut issues);
+                if let Some(json) = json.filter(|_| TEXTURE_LISTS.contains(&path.as_str())) {
+                    lint_texture_list(path, &json, &files_set, &lowercase_files, &mut issues);
+                }
+            }
+            "png" | "tga" => lint_texture_size(storage, path, &extension, &mut issues),
+            _ => {}
+        }
+
+        lint_ignored_file(path, &extension, &mut issues);
+    }
+
+    Ok(issues)
+}
+
+fn issue(severity: LintSeverity, path: &str, message: String) -> LintIssue {
+    LintIssue {
+        severity,
+        path: path.to_owned(),
+        message,
+    }
+}
+
+fn lint_manifest(storage: &dyn PackStorage, issues: &mut Vec<LintIssue>) {
+    let manifest = match read_storage_manifest(storage) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            issues.push(issue(LintSeverity::Error, "manifest.json", error_chain(&e)));
+            return;
+        }
+    };
+
+    if let Err(ManifestError::InvalidManifest(errors)) = manifest.validate() {
+        for e in errors {
+            issues.push(issue(LintSeverity::Error, "manifest.json", e.to_string()));
+        }
+    }
+
+    if let Err(e) = validate_subpack_folders(storage, &manifest) {
+        issues.push(issue(LintSeverity::Error, "manifest.json", e));
+    }
+}
+
+// Paths that differ only by case collide on Windows and macOS
+fn lint_case_collisions(files: &[String], issues: &mut Vec<LintIssue>) {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for path in files {
+        if let Some(other) = seen.insert(path.to_lowercase(), path) {
+            issues.push(issue(
+                LintSeverity::Error,
+                path,
+                format!("Path differs from {} only by case", other),
+            ));
+        }
+    }
+}
+
+fn lint_json(storage: &dyn PackStorage, path: &str, issues: &mut Vec<LintIssue>) -> Option<Value> {
+    let content = match storage.read(Path::new(path)).map(String::from_utf8) {
+        Ok(Ok(content)) => content,
+        Ok(Err(_)) => {
+            issues.push(issue(
+                LintSeverity::Error,
+                path,
+                String::from("File is not valid UTF-8"),
+            ));
+            return None;
+        }
+        Err(e) => {
+            issues.push(issue(
+                LintSeverity::Error,
+                path,
+                format!("Can't read file: {}", e),
+            ));
+            return None;
+        }
+    };
+
+    parse_jsonc(&content)
+        .map_err(|e| {
+            issues.push(issue(
+                LintSeverity::Error,
+                path,
+                format!("Invalid json: {}", e),
+            ))
+        })
+        .ok()
+}
+
+// texture_data: { "name": { "textures": entry | [entry] } }, where entry is
+// "path", { "path": "path" } or { "variations": [{ "path": "path" }] }
+fn lint_texture_list(
+    path: &str,
+    json: &Value,
+    files: &HashSet<&str>,
+    lowercase_files: &HashMap<String, &str>,
+    issues: &mut Vec<LintIssue>,
+) {
+    let Some(texture_data) = json["texture_data"].as_object() else {
+        issues.push(issue(
+            LintSeverity::Error,
+            path,
+            String::from("Missing texture_data object"),
+        ));
+        return;
+    };
+
+    for (name, texture) in texture_data {
+        let textures = match &texture["textures"] {
+            Value::Array(textures) => textures.iter().collect(),
+            textures => vec![textures],
+        };
+
+        let mut references = Vec::new();
+        for entry in textures {
+            if !collect_texture_references(entry, &mut references) {
+                issues.push(issue(
+                    LintSeverity::Error,
+                    path,
+                    format!("Texture \"{}\" has invalid path", name),
+                ));
+            }
+        }
+
+        for reference in references {
+            if let Some((severity, message)) =
+                check_texture_reference(reference, files, lowercase_files)
+            {
+                issues.push(issue(
+                    severity,
+                    path,
+                    format!("Texture \"{}\": {}", name, message),
+                ));
+            }
+        }
+    }
+}
+
+// Returns false if entry has no path at all
+fn collect_texture_references<'a>(entry: &'a Value, references: &mut Vec<&'a str>) -> bool {
+    match entry {
+        Value::String(reference) => {
+            references.push(reference);
+            true
+        }
+        Value::Object(object) => match (object.get("path"), object.get("variations")) {
+            (Some(Value::String(reference)), _) => {
+                references.push(reference);
+                true
+            }
+            (None, Some(Value::Array(variations))) => variations
+                .iter()
+                .all(|variation| collect_texture_references(variation, references)),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// Returns problem description if reference is broken
+fn check_texture_reference(
+    reference: &str,
+    files: &HashSet<&str>,
+    lowercase_files: &HashMap<String, &str>,
+) -> Option<(LintSeverity, String)> {
+    // Reference can be with or without extension
+    let candidates = std::iter::once(reference.to_owned())
+        .chain(
+            TEXTURE_EXTENSIONS
+                .iter()
+                .map(|ext| format!("{}.{}", reference, ext)),
+        )
+        .collect::<Vec<_>>();
+
+    if candidates
+        .iter()
+        .any(|candidate| files.contains(candidate.as_str()))
+    {
+        return None;
+    }
+
+    match candidates
+        .iter()
+        .find_map(|candidate| lowercase_files.get(&candidate.to_lowercase()))
+    {
+        Some(found) => Some((
+            LintSeverity::Error,
+            format!("Case mismatch, \"{}\" references {}", reference, found),
+        )),
+        // Packs often reference vanilla textures, which aren't part of the pack
+        None => Some((
+            LintSeverity::Warning,
+            format!(
+                "Texture file \"{}\" is not in the pack, make sure it is a vanilla texture",
+                reference
+            ),
+        )),
+    }
+}
+
+fn lint_texture_size(
+    storage: &dyn PackStorage,
+    path: &str,
+    extension: &str,
+    issues: &mut Vec<LintIssue>,
+) {
+    // Both formats keep size in the first 24 bytes
+    let Ok(header) = storage.read_prefix(Path::new(path), 24) else {
+        return;
+    };
+
+    // Broken textures are only warnings, Bedrock shows them as missing ones
+    let size = match extension {
+        "png" if !header.starts_with(b"\x89PNG\r\n\x1a\n") => Err("File is not a valid PNG image"),
+        "png" if header.len() < 24 => Err("PNG header is truncated"),
+        // Signature, IHDR chunk length and type, then big-endian width and height
+        "png" => Ok((
+            u32::from_be_bytes([header[16], header[17], header[18], header[19]]),
+            u32::from_be_bytes([header[20], header[21], header[22], header[23]]),
+        )),
+        // Header of TGA is 18 bytes
+        _ if header.len() < 18 => Err("TGA header is truncated"),
+        _ => Ok((
+            u16::from_le_bytes([header[12], header[13]]) as u32,
+            u16::from_le_bytes([header[14], header[15]]) as u32,
+        )),
+    };
+
+    match size {
+        Ok((width, height)) if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE => issues
+            .push(issue(
+                LintSeverity::Warning,
+                path,
+                format!(
+                    "Texture is {}x{}, larger than {}x{}",
+                    width, height, MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE
+                ),
+            )),
+        Ok(_) => {}
+        Err(message) => issues.push(issue(LintSeverity::Warning, path, String::from(message))),
+    }
+}
+
+fn lint_ignored_file(path: &str, extension: &str, issues: &mut Vec<LintIssue>) {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    let message = if JUNK_FILES.contains(&file_name) || file_name.starts_with('.') {
+        "System or hidden file, Bedrock ignores it"
+    } else if !KNOWN_EXTENSIONS.contains(&extension) {
+        "Unknown file type, Bedrock ignores it"
+    } else {
+        return;
+    };
+
+    issues.push(issue(LintSeverity::Warning, path, String::from(message)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::fixtures::{KEY, MANIFEST};
+    use crate::packs::storage::MemoryStorage;
+    use std::collections::BTreeMap;
+
+    // Only the header of PNG matters for linter
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png
+    }
+
+    // Issues of a pack with valid manifest and given files
+    fn lint(files: &[(&str, &[u8])]) -> Vec<LintIssue> {
+        let mut pack = BTreeMap::new();
+        pack.insert(String::from("manifest.json"), MANIFEST.as_bytes().to_vec());
+        for (path, content) in files {
+            pack.insert(path.to_string(), content.to_vec());
+        }
+        lint_storage(&MemoryStorage::from(pack)).unwrap()
+    }
+
+    fn found(issues: &[LintIssue], severity: LintSeverity, path: &str, message: &str) -> bool {
+        issues.iter().any(|issue| {
+            issue.severity == severity && issue.path == path && issue.message.contains(message)
+        })
+    }
+
+    fn terrain_texture(texture_data: &str) -> Vec<LintIssue> {
+        let json = format!(r#"{{ "texture_data": {} }}"#, texture_data);
+        lint(&[
+            ("textures/terrain_texture.json", json.as_bytes()),
+            ("textures/blocks/stone.png", &png(16, 16)),
+        ])
+    }
+
+    #[test]
+    fn valid_pack_has_no_issues() {
+        let issues = terrain_texture(r#"{ "stone": { "textures": "textures/blocks/stone" } }"#);
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn missing_manifest() {
+        let issues = lint_storage(&MemoryStorage::new()).unwrap();
+        assert!(found(&issues, LintSeverity::Error, "manifest.json", ""));
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        let issues = lint_storage(&MemoryStorage::from(BTreeMap::from([(
+            String::from("manifest.json"),
+            br#"{ "header": { "uuid": "not a uuid" } }"#.to_vec(),
+        )])))
+        .unwrap();
+        assert!(found(&issues, LintSeverity::Error, "manifest.json", ""));
+    }
+
+    #[test]
+    fn case_collisions() {
+        let issues = lint(&[("texts/en_US.lang", b""), ("texts/en_us.lang", b"")]);
+        assert!(issues
+            .iter()
+            .any(|issue| issue.severity == LintSeverity::Error
+                && issue.message.contains("only by case")));
+    }
+
+    #[test]
+    fn broken_json() {
+        let issues = lint(&[
+            ("entity/broken.json", b"{ \"a\": "),
+            ("entity/binary.json", b"\xff\xfe"),
+            // Comments are fine
+            ("entity/comments.json", b"{ // comment\n \"a\": 1 }"),
+        ]);
+        assert!(found(
+            &issues,
+            LintSeverity::Error,
+            "entity/broken.json",
+            "Invalid json"
+        ));
+        assert!(found(
+            &issues,
+            LintSeverity::Error,
+            "entity/binary.json",
+            "UTF-8"
+        ));
+        assert!(!issues
+            .iter()
+            .any(|issue| issue.path == "entity/comments.json"));
+    }
+
+    #[test]
+    fn missing_texture_data() {
+        let issues = lint(&[("textures/item_texture.json", b"{}")]);
+        assert!(found(
+            &issues,
+            LintSeverity::Error,
+            "textures/item_texture.json",
+            "Missing texture_data"
+        ));
+    }
+
+    #[test]
+    fn vanilla_texture_is_warning() {
+        let issues = terrain_texture(r#"{ "dirt": { "textures": "textures/blocks/dirt" } }"#);
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/terrain_texture.json",
+            "textures/blocks/dirt"
+        ));
+        assert!(!issues
+            .iter()
+            .any(|issue| issue.severity == LintSeverity::Error));
+    }
+
+    #[test]
+    fn texture_case_mismatch() {
+        let issues = terrain_texture(r#"{ "stone": { "textures": ["textures/blocks/Stone"] } }"#);
+        assert!(found(
+            &issues,
+            LintSeverity::Error,
+            "textures/terrain_texture.json",
+            "Case mismatch"
+        ));
+    }
+
+    #[test]
+    fn texture_entries() {
+        let issues = terrain_texture(
+            r##"{
+                "path": { "textures": { "path": "textures/blocks/stone.png", "overlay_color": "#ffffff" } },
+                "variations": { "textures": { "variations": [
+                    { "path": "textures/blocks/stone", "weight": 10 },
+                    { "path": "textures/blocks/andesite", "weight": 1 }
+                ] } },
+                "broken": { "textures": [{ "weight": 1 }, 42] }
+            }"##,
+        );
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/terrain_texture.json",
+            "textures/blocks/andesite"
+        ));
+        let invalid = issues
+            .iter()
+            .filter(|issue| issue.message.contains("invalid path"))
+            .collect::<Vec<_>>();
+        assert_eq!(invalid.len(), 2, "{:?}", issues);
+        assert!(invalid
+            .iter()
+            .all(|issue| issue.message.contains("\"broken\"")));
+    }
+
+    #[test]
+    fn texture_size() {
+        let mut tga = vec![0; 18];
+        tga[12..14].copy_from_slice(&4096u16.to_le_bytes());
+        tga[14..16].copy_from_slice(&16u16.to_le_bytes());
+
+        let issues = lint(&[
+            ("textures/big.png", &png(4096, 4096)),
+            ("textures/big.tga", &[tga, vec![0; 6]].concat()),
+            ("textures/fake.png", b"not an image, just long enough text"),
+            ("textures/short.png", &png(16, 16)[..20]),
+            ("textures/short.tga", &[0; 10]),
+        ]);
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/big.png",
+            "4096x4096"
+        ));
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/big.tga",
+            "4096x16"
+        ));
+        // Broken textures don't stop encryption
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/fake.png",
+            "not a valid PNG"
+        ));
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/short.png",
+            "PNG header is truncated"
+        ));
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/short.tga",
+            "TGA header is truncated"
+        ));
+        assert!(issues
+            .iter()
+            .all(|issue| issue.severity == LintSeverity::Warning));
+    }
+
+    #[test]
+    fn encrypted_pack() {
+        let encrypted = crate::utils::cipher::aes256_cbf8_encrypt(KEY, png(16, 16)).unwrap();
+        let issues = lint(&[
+            ("textures/a.png", &encrypted),
+            ("textures/b.png", &encrypted),
+            ("textures/broken.json", b"{ not json"),
+        ]);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "textures/a.png",
+            "Pack is encrypted"
+        ));
+    }
+
+    #[test]
+    fn ignored_files() {
+        let issues = lint(&[
+            (".DS_Store", b""),
+            ("textures/Thumbs.db", b""),
+            ("notes.txt", b""),
+        ]);
+        for path in [".DS_Store", "textures/Thumbs.db"] {
+            assert!(found(
+                &issues,
+                LintSeverity::Warning,
+                path,
+                "System or hidden"
+            ));
+        }
+        assert!(found(
+            &issues,
+            LintSeverity::Warning,
+            "notes.txt",
+            "Unknown file type"
+        ));
+    }
+}
@@ -1,33 +1,490 @@
-use crate::utils::comments::clear_comments;
+use crate::packs::pack_encryption::{FileError, FileOperation};
+use crate::packs::storage::{DirectoryStorage, PackStorage};
+use crate::utils::jsonc::{parse_jsonc, JsoncError};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashSet;
+use std::error::Error;
 use std::ffi::OsString;
-use std::fs;
-use std::fs::metadata;
-use std::path::PathBuf;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
-pub fn get_uuid_from_manifest(target_directory: &OsString) -> Result<String, String> {
-    let mut path = PathBuf::from(target_directory);
-    path.push("manifest.json");
+// Typed model of manifest.json. Only fields Minecraft actually
+// reads are here, everything else is ignored while parsing.
+// Parsing is lenient, so callers that need only UUID can read any manifest,
+// required fields are checked by validate
 
-    if let Ok(file_metadata) = metadata(&path) {
-        if !file_metadata.is_file() {
-            return Err(format!("Path is not a file: {}", path.display()));
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Manifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format_version: Option<u32>,
+    pub header: ManifestHeader,
+    #[serde(default)]
+    pub modules: Vec<ManifestModule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<ManifestDependency>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subpacks: Vec<ManifestSubpack>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ManifestMetadata>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestHeader {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<ManifestVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_engine_version: Option<ManifestVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestModule {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub module_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<ManifestVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Script modules only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+// Dependency is either another pack (uuid) or script module (module_name)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestDependency {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<ManifestVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestSubpack {
+    pub folder_name: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_tier: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_performance_tier: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_with: Option<Value>,
+}
+
+// Format version 1 and 2 use [1, 0, 0], format version 3 allows "1.0.0"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ManifestVersion {
+    Array([u32; 3]),
+    Semver(String),
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    NotFound,
+    NotAFile(PathBuf),
+    ReadError(FileError),
+    // Broken json, with line and column
+    SyntaxError(JsoncError),
+    // Valid json that doesn't match manifest structure
+    ParseError(serde_json::Error),
+    InvalidManifest(Vec<ManifestValidationError>),
+}
+
+#[derive(Debug)]
+pub enum ManifestValidationError {
+    // Name of required field
+    MissingField(String),
+    UnsupportedFormatVersion(u32),
+    EmptyName,
+    // Field name and value
+    InvalidUuid(String, String),
+    InvalidVersion(String, String),
+    MissingModules,
+    DuplicateUuid(String),
+    // Index of dependency without uuid and module_name
+    InvalidDependency(usize),
+    DuplicateSubpack(String),
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::NotFound => write!(f, "No manifest.json file in provided directory"),
+            ManifestError::NotAFile(path) => write!(f, "Path is not a file: {}", path.display()),
+            ManifestError::ReadError(e) => write!(f, "{}", e),
+            ManifestError::SyntaxError(_) => write!(f, "Can't parse manifest file"),
+            ManifestError::ParseError(_) => write!(f, "Manifest file has invalid structure"),
+            ManifestError::InvalidManifest(errors) => {
+                write!(f, "Manifest file is invalid:")?;
+                for e in errors {
+                    write!(f, "\n  - {}", e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ManifestError::ReadError(e) => Some(e.source.as_ref()),
+            ManifestError::SyntaxError(e) => Some(e),
+            ManifestError::ParseError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ManifestValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestValidationError::MissingField(field) => write!(f, "{} is missing", field),
+            ManifestValidationError::UnsupportedFormatVersion(version) => {
+                write!(f, "Unsupported format_version {}", version)
+            }
+            ManifestValidationError::EmptyName => write!(f, "header.name is missing or empty"),
+            ManifestValidationError::InvalidUuid(field, uuid) => {
+                write!(f, "{} is not a valid UUID: \"{}\"", field, uuid)
+            }
+            ManifestValidationError::InvalidVersion(field, version) => {
+                write!(f, "{} is not a valid version: \"{}\"", field, version)
+            }
+            ManifestValidationError::MissingModules => write!(f, "No modules specified"),
+            ManifestValidationError::DuplicateUuid(uuid) => {
+                write!(f, "UUID {} is used more than once", uuid)
+            }
+            ManifestValidationError::InvalidDependency(i) => write!(
+                f,
+                "dependencies[{}] must have either uuid or module_name",
+                i
+            ),
+            ManifestValidationError::DuplicateSubpack(folder_name) => {
+                write!(f, "Subpack folder {} is listed more than once", folder_name)
+            }
+        }
+    }
+}
+
+pub fn read_manifest(target_directory: &OsString) -> Result<Manifest, ManifestError> {
+    read_storage_manifest(&DirectoryStorage::new(target_directory))
+}
+
+pub fn read_storage_manifest(storage: &dyn PackStorage) -> Result<Manifest, ManifestError> {
+    let path = Path::new("manifest.json");
+    if !storage.exists(path) {
+        return Err(ManifestError::NotFound);
+    }
+    if storage.is_dir(path) {
+        let full_path = storage.local_path().unwrap_or_default().join(path);
+        return Err(ManifestError::NotAFile(full_path));
+    }
+
+    let manifest_content = storage
+        .read(path)
+        .and_then(|bytes| {
+            String::from_utf8(bytes).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))
+        })
+        .map_err(|e| {
+            ManifestError::ReadError(FileError::new("manifest.json", FileOperation::Read, e))
+        })?;
+    parse_manifest(&manifest_content)
+}
+
+// Manifest comes from the pack, so any text must give either manifest or error
+pub fn parse_manifest(content: &str) -> Result<Manifest, ManifestError> {
+    let json = parse_jsonc(content).map_err(ManifestError::SyntaxError)?;
+    serde_json::from_value(json).map_err(ManifestError::ParseError)
+}
+
+impl Manifest {
+    // Collects all problems at once, so user can fix manifest in one go
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        let mut errors = Vec::new();
+
+        match self.format_version {
+            None => errors.push(ManifestValidationError::MissingField(String::from(
+                "format_version",
+            ))),
+            Some(version) if !(1..=3).contains(&version) => {
+                errors.push(ManifestValidationError::UnsupportedFormatVersion(version))
+            }
+            Some(_) => {}
         }
-    } else {
-        return Err(String::from("No manifest.json file in provided directory"));
+
+        if self.header.name.trim().is_empty() {
+            errors.push(ManifestValidationError::EmptyName);
+        }
+
+        validate_uuid("header.uuid", &self.header.uuid, &mut errors);
+        match &self.header.version {
+            Some(version) => validate_version("header.version", version, &mut errors),
+            None => errors.push(ManifestValidationError::MissingField(String::from(
+                "header.version",
+            ))),
+        }
+        if let Some(version) = &self.header.min_engine_version {
+            validate_version("header.min_engine_version", version, &mut errors);
+        }
+
+        if self.modules.is_empty() {
+            errors.push(ManifestValidationError::MissingModules);
+        }
+
+        // Minecraft refuses to load pack if header and modules share uuid
+        let mut seen_uuids = HashSet::from([self.header.uuid.to_lowercase()]);
+        for (i, module) in self.modules.iter().enumerate() {
+            if module.module_type.is_none() {
+                errors.push(ManifestValidationError::MissingField(format!(
+                    "modules[{}].type",
+                    i
+                )));
+            }
+
+            let field = format!("modules[{}].uuid", i);
+            match &module.uuid {
+                Some(uuid) => validate_uuid(&field, uuid, &mut errors),
+                None => errors.push(ManifestValidationError::MissingField(field)),
+            }
+            let field = format!("modules[{}].version", i);
+            match &module.version {
+                Some(version) => validate_version(&field, version, &mut errors),
+                None => errors.push(ManifestValidationError::MissingField(field)),
+            }
+
+            if let Some(uuid) = &module.uuid {
+                if !seen_uuids.insert(uuid.to_lowercase()) {
+                    errors.push(ManifestValidationError::DuplicateUuid(uuid.clone()));
+                }
+            }
+        }
+
+        for (i, dependency) in self.dependencies.iter().enumerate() {
+            match (&dependency.uuid, &dependency.module_name) {
+                (Some(uuid), _) => {
+                    validate_uuid(&format!("dependencies[{}].uuid", i), uuid, &mut errors)
+                }
+                (None, Some(_)) => {}
+                (None, None) => errors.push(ManifestValidationError::InvalidDependency(i)),
+            }
+            if let Some(version) = &dependency.version {
+                validate_version(
+                    &format!("dependencies[{}].version", i),
+                    version,
+                    &mut errors,
+                );
+            }
+        }
+
+        let mut seen_subpacks = HashSet::new();
+        for subpack in &self.subpacks {
+            if !seen_subpacks.insert(&subpack.folder_name) {
+                errors.push(ManifestValidationError::DuplicateSubpack(
+                    subpack.folder_name.clone(),
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ManifestError::InvalidManifest(errors))
+        }
+    }
+}
+
+// 8-4-4-4-12 hex digits
+fn is_valid_uuid(uuid: &str) -> bool {
+    let bytes = uuid.as_bytes();
+    bytes.len() == 36
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            8 | 13 | 18 | 23 => *byte == b'-',
+            _ => byte.is_ascii_hexdigit(),
+        })
+}
+
+fn validate_uuid(field: &str, uuid: &str, errors: &mut Vec<ManifestValidationError>) {
+    if !is_valid_uuid(uuid) {
+        errors.push(ManifestValidationError::InvalidUuid(
+            field.to_owned(),
+            uuid.to_owned(),
+        ));
+    }
+}
+
+fn validate_version(
+    field: &str,
+    version: &ManifestVersion,
+    errors: &mut Vec<ManifestValidationError>,
+) {
+    let ManifestVersion::Semver(version) = version else {
+        return;
+    };
+
+    // Pre-release and build parts like "1.0.0-beta" are allowed
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts = core.split('.').collect::<Vec<_>>();
+
+    if parts.len() != 3 || parts.iter().any(|part| part.parse::<u32>().is_err()) {
+        errors.push(ManifestValidationError::InvalidVersion(
+            field.to_owned(),
+            version.to_owned(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::fixtures::{MANIFEST, MODULE_UUID, UUID};
+
+    fn validation_errors(content: &str) -> Vec<String> {
+        match parse_manifest(content).unwrap().validate() {
+            Ok(()) => Vec::new(),
+            Err(ManifestError::InvalidManifest(errors)) => {
+                errors.iter().map(ToString::to_string).collect()
+            }
+            Err(e) => panic!("unexpected error {}", e),
+        }
+    }
+
+    #[test]
+    fn only_uuid_is_needed_to_parse() {
+        let manifest =
+            parse_manifest(&format!(r#"{{ "header": {{ "uuid": "{}" }} }}"#, UUID)).unwrap();
+        assert_eq!(manifest.header.uuid, UUID);
+        assert_eq!(manifest.format_version, None);
+        assert_eq!(manifest.header.version, None);
+
+        assert!(matches!(
+            parse_manifest(r#"{ "header": { "name": "No UUID" } }"#),
+            Err(ManifestError::ParseError(_))
+        ));
+        assert!(matches!(
+            parse_manifest("{ header"),
+            Err(ManifestError::SyntaxError(_))
+        ));
     }
 
-    let mut manifest_content =
-        fs::read_to_string(&path).map_err(|e| format!("Can't read manifest file: {}", e))?;
+    #[test]
+    fn required_fields_are_validated() {
+        let errors = validation_errors(&format!(
+            r#"{{ "header": {{ "uuid": "{}" }}, "modules": [{{ "type": "resources", "uuid": "{}", "version": [1, 0, 0] }}] }}"#,
+            UUID, MODULE_UUID
+        ));
+        assert_eq!(
+            errors,
+            [
+                "format_version is missing",
+                "header.name is missing or empty",
+                "header.version is missing"
+            ]
+        );
+    }
 
-    clear_comments(&mut manifest_content);
+    #[test]
+    fn module_fields_are_validated() {
+        let errors = validation_errors(&format!(
+            r#"{{
+                "format_version": 2,
+                "header": {{ "name": "Pack", "uuid": "{}", "version": [1, 0, 0] }},
+                "modules": [
+                    {{ "type": "resources", "uuid": "{}" }},
+                    {{ "version": [1, 0, 0] }}
+                ]
+            }}"#,
+            UUID, MODULE_UUID
+        ));
+        assert_eq!(
+            errors,
+            [
+                "modules[0].version is missing",
+                "modules[1].type is missing",
+                "modules[1].uuid is missing"
+            ]
+        );
+    }
 
-    let json: Value = serde_json::from_str(&manifest_content)
-        .map_err(|e| format!("Can't parse manifest file: {}", e))?;
+    #[test]
+    fn valid_manifest() {
+        assert!(validation_errors(MANIFEST).is_empty());
 
-    let uuid = json["header"]["uuid"]
-        .as_str()
-        .ok_or("Can't get uuid from manifest.json")?;
+        let content = format!(
+            r#"{{
+                // Comments are allowed
+                "format_version": 3,
+                "header": {{ "name": "Pack", "uuid": "{}", "version": "1.2.3-beta" }},
+                "modules": [{{ "type": "resources", "uuid": "{}", "version": [1, 0, 0] }}],
+                "unknown": true
+            }}"#,
+            UUID, MODULE_UUID
+        );
+        assert!(validation_errors(&content).is_empty());
 
-    Ok(uuid.to_owned())
+        // Missing optional fields aren't written back
+        let json = serde_json::to_value(parse_manifest(&content).unwrap()).unwrap();
+        assert!(json.get("metadata").is_none());
+        assert_eq!(json["header"]["version"], "1.2.3-beta");
+    }
+
+    #[test]
+    fn invalid_values() {
+        let errors = validation_errors(&format!(
+            r#"{{
+                "format_version": 4,
+                "header": {{ "name": " ", "uuid": "{}", "version": "1.0" }},
+                "modules": [{{ "type": "resources", "uuid": "{}", "version": [1, 0, 0] }}],
+                "dependencies": [{{ "version": [1, 0, 0] }}],
+                "subpacks": [
+                    {{ "folder_name": "low", "name": "Low" }},
+                    {{ "folder_name": "low", "name": "Also low" }}
+                ]
+            }}"#,
+            UUID,
+            UUID.to_uppercase()
+        ));
+        assert_eq!(
+            errors,
+            [
+                "Unsupported format_version 4".to_owned(),
+                "header.name is missing or empty".to_owned(),
+                "header.version is not a valid version: \"1.0\"".to_owned(),
+                format!("UUID {} is used more than once", UUID.to_uppercase()),
+                "dependencies[0] must have either uuid or module_name".to_owned(),
+                "Subpack folder low is listed more than once".to_owned(),
+            ]
+        );
+        assert_eq!(
+            validation_errors(&format!(
+                r#"{{ "format_version": 2, "header": {{ "name": "Pack", "uuid": "{}", "version": [1, 0, 0] }} }}"#,
+                UUID
+            )),
+            ["No modules specified"]
+        );
+    }
 }
@@ -0,0 +1,134 @@
+use crate::packs::pack_encryption::{
+    parallel_processing, FileError, FileOperation, ParallelProcessingError, ProcessingOptions,
+};
+use crate::packs::progress::{ProgressCallback, ProgressStage};
+use crate::packs::storage::PackStorage;
+use crate::utils::jsonc::parse_jsonc;
+use serde::Serialize;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Optional pre-encryption stage. Bedrock packs are full of pretty-printed
+// json with comments, so minifying it makes pack smaller and hides its structure
+
+// Materials are json too
+const MINIFIED_EXTENSIONS: [&str; 2] = ["json", "material"];
+
+#[derive(Debug, Default, Serialize)]
+pub struct MinifyStats {
+    pub files: usize,
+    pub saved_bytes: u64,
+    // Files that can't be parsed and were left as is
+    pub skipped: Vec<FileError>,
+}
+
+fn should_minify(path: &Path) -> bool {
+    // Old contents.json is regenerated anyway, and manifest.json stays
+    // readable in encrypted pack, so its comments and formatting are kept
+    path != Path::new("contents.json")
+        && path != Path::new("manifest.json")
+        && path.extension().is_some_and(|ext| {
+            MINIFIED_EXTENSIONS
+                .iter()
+                .any(|e| ext.to_str().is_some_and(|ext| ext.eq_ignore_ascii_case(e)))
+        })
+}
+
+// With strict = true invalid json fails the whole stage,
+// otherwise such files are skipped
+pub fn minify_json_files(
+    storage: &dyn PackStorage,
+    relative_paths: &[PathBuf],
+    strict: bool,
+    processing: &ProcessingOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<MinifyStats, ParallelProcessingError<FileError>> {
+    let tasks = relative_paths
+        .iter()
+        .filter(|path| should_minify(path))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let files = AtomicUsize::new(0);
+    let saved_bytes = AtomicU64::new(0);
+    let skipped = Mutex::new(Vec::new());
+
+    let progress = progress.map(|callback| (ProgressStage::Minify, callback));
+
+    parallel_processing(
+        tasks,
+        |rel_path| {
+            let path = rel_path.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+
+            let content = storage
+                .read(&rel_path)
+                .and_then(|bytes| {
+                    String::from_utf8(bytes)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                })
+                .map_err(|e| FileError::new(path.clone(), FileOperation::Read, e))?;
+
+            let json = match parse_jsonc(&content) {
+                Ok(json) => json,
+                Err(e) => {
+                    let e = FileError::new(path, FileOperation::Minify, e);
+                    if strict {
+                        return Err(e);
+                    }
+                    skipped.lock().unwrap().push(e);
+                    return Ok(());
+                }
+            };
+
+            let minified = serde_json::to_vec(&json)
+                .map_err(|e| FileError::new(path.clone(), FileOperation::Minify, e))?;
+
+            storage
+                .write(&rel_path, &minified)
+                .map_err(|e| FileError::new(path, FileOperation::Write, e))?;
+
+            files.fetch_add(1, Ordering::Relaxed);
+            saved_bytes.fetch_add(
+                (content.len() as u64).saturating_sub(minified.len() as u64),
+                Ordering::Relaxed,
+            );
+            Ok(())
+        },
+        processing,
+        progress,
+    )?;
+
+    let skipped = skipped.into_inner().unwrap();
+    Ok(MinifyStats {
+        files: files.load(Ordering::Relaxed),
+        saved_bytes: saved_bytes.load(Ordering::Relaxed),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minified_files() {
+        for path in [
+            "models/entity/cow.json",
+            "materials/a.MATERIAL",
+            "UI/Hud.JSON",
+        ] {
+            assert!(should_minify(Path::new(path)), "{}", path);
+        }
+        for path in [
+            "manifest.json",
+            "contents.json",
+            "texts/en_US.lang",
+            "pack_icon.png",
+        ] {
+            assert!(!should_minify(Path::new(path)), "{}", path);
+        }
+        // Only manifest of the pack itself is kept as is
+        assert!(should_minify(Path::new("subpacks/low/manifest.json")));
+    }
+}
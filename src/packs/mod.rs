@@ -1,5 +1,22 @@
-mod contents;
-mod manifest;
+pub mod archive;
+#[cfg(feature = "cli")]
+pub mod build;
+#[cfg(feature = "cli")]
+pub mod config;
+pub mod contents;
+pub mod detect;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod inspect;
+pub mod lint;
+pub mod manifest;
+pub mod minify;
 pub mod pack_encryption;
+pub mod progress;
+pub mod storage;
+pub mod subpacks;
+pub mod verify;
+#[cfg(feature = "cli")]
+pub mod watch;
 
 pub mod encryption;
@@ -1,10 +1,21 @@
-use crate::utils::cipher::{generate_random_key, CipherError};
-use crate::utils::cli::get_input;
+use crate::packs::contents::UnsafePath;
+use crate::packs::detect::EncryptionEvidence;
+use crate::packs::lint::LintIssue;
+use crate::packs::manifest::ManifestError;
+use crate::packs::progress::{ProgressCallback, ProgressEvent, ProgressItem, ProgressStage};
+use crate::utils::cipher::CipherError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::json;
+use std::any::Any;
 use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
 use std::fs::{read_dir, File};
 use std::io::{Error, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 use std::string::FromUtf8Error;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 
@@ -15,13 +26,236 @@ pub enum PackEncryptionError {
     ContentsDecodingError(FromUtf8Error),
     ContentsGeneratingError(Error),
     JsonError(serde_json::Error),
-    ProcessingError(Vec<String>),
+    ProcessingError(Vec<FileError>),
     DataCollectionError(String),
     CipherError(CipherError),
     FileSystemError(Error),
+    // Error of contents.json, manifest.json or another single file
+    FileError(FileError),
+    ArchiveError(zip::result::ZipError),
+    ManifestError(ManifestError),
+    LintError(Vec<LintIssue>),
+    AlreadyEncrypted(EncryptionEvidence),
+    // Entries of contents.json leading outside of the pack
+    UnsafePaths(Vec<UnsafePath>),
+    // Errors of files processed before cancellation
+    Cancelled(Vec<FileError>),
+    // Encryption stopped after some files were encrypted
+    PartiallyEncrypted(PartialEncryption),
+    // Panic message and errors of files processed before it
+    WorkerPanicked(String, Vec<FileError>),
     Abort,
 }
 
+impl Display for PackEncryptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackEncryptionError::ContentsDecodingError(_) => {
+                write!(
+                    f,
+                    "Decrypted contents.json is not valid UTF-8, probably key is wrong"
+                )
+            }
+            PackEncryptionError::ContentsGeneratingError(_) => {
+                write!(f, "Can't generate contents.json")
+            }
+            PackEncryptionError::JsonError(_) => write!(f, "Can't parse contents.json"),
+            PackEncryptionError::ProcessingError(errors) => {
+                write!(f, "{} files failed:", errors.len())?;
+                for e in errors {
+                    write!(f, "\n  - {}", error_chain(e))?;
+                }
+                Ok(())
+            }
+            PackEncryptionError::DataCollectionError(message) => write!(f, "{}", message),
+            PackEncryptionError::CipherError(_) => write!(f, "Can't process contents.json"),
+            PackEncryptionError::FileSystemError(_) => write!(f, "File system error"),
+            PackEncryptionError::FileError(e) => write!(f, "{}", e),
+            PackEncryptionError::ArchiveError(_) => write!(f, "Can't process archive"),
+            PackEncryptionError::ManifestError(_) => write!(f, "Can't use manifest.json"),
+            PackEncryptionError::LintError(issues) => {
+                write!(f, "Linter found {} errors:", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  - {}", issue)?;
+                }
+                Ok(())
+            }
+            PackEncryptionError::AlreadyEncrypted(evidence) => write!(
+                f,
+                "Pack is already encrypted: {}. Encrypting it again would make it unrecoverable without both keys",
+                evidence
+            ),
+            PackEncryptionError::UnsafePaths(paths) => {
+                write!(
+                    f,
+                    "contents.json lists {} paths outside of the pack, nothing was decrypted:",
+                    paths.len()
+                )?;
+                for path in paths {
+                    write!(f, "\n  - {}", path)?;
+                }
+                Ok(())
+            }
+            PackEncryptionError::Cancelled(errors) => {
+                write!(f, "Cancelled, pack is left partially processed")?;
+                for e in errors {
+                    write!(f, "\n  - {}", error_chain(e))?;
+                }
+                Ok(())
+            }
+            PackEncryptionError::PartiallyEncrypted(partial) => {
+                write!(
+                    f,
+                    "{}\nPack is left partially encrypted, contents.json lists only encrypted files. Decrypt it with the same key before encrypting again. {} files are left unencrypted:",
+                    error_chain(partial.cause.as_ref()),
+                    partial.unencrypted.len()
+                )?;
+                for path in &partial.unencrypted {
+                    write!(f, "\n  - {}", path)?;
+                }
+                Ok(())
+            }
+            PackEncryptionError::WorkerPanicked(message, errors) => {
+                write!(f, "Worker thread panicked: {}", message)?;
+                for e in errors {
+                    write!(f, "\n  - {}", error_chain(e))?;
+                }
+                Ok(())
+            }
+            PackEncryptionError::Abort => write!(f, "Aborted by user"),
+        }
+    }
+}
+
+impl PackEncryptionError {
+    // Extra fields for machine readable output, like per-file errors
+    pub fn details(&self) -> serde_json::Value {
+        match self {
+            PackEncryptionError::ProcessingError(errors)
+            | PackEncryptionError::Cancelled(errors)
+            | PackEncryptionError::WorkerPanicked(_, errors) => json!({ "files": errors }),
+            PackEncryptionError::LintError(issues) => json!({ "lint": issues }),
+            PackEncryptionError::AlreadyEncrypted(evidence) => json!({ "evidence": evidence }),
+            PackEncryptionError::FileError(e) => json!({ "files": [e] }),
+            PackEncryptionError::UnsafePaths(paths) => json!({ "unsafe_paths": paths }),
+            PackEncryptionError::PartiallyEncrypted(partial) => {
+                let mut details = partial.cause.details();
+                if !details.is_object() {
+                    details = json!({});
+                }
+                details["unencrypted"] = json!(partial.unencrypted);
+                details
+            }
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+impl std::error::Error for PackEncryptionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PackEncryptionError::ContentsDecodingError(e) => Some(e),
+            PackEncryptionError::ContentsGeneratingError(e) => Some(e),
+            PackEncryptionError::JsonError(e) => Some(e),
+            PackEncryptionError::CipherError(e) => Some(e),
+            PackEncryptionError::FileSystemError(e) => Some(e),
+            PackEncryptionError::FileError(e) => Some(e.source.as_ref()),
+            PackEncryptionError::ArchiveError(e) => Some(e),
+            PackEncryptionError::ManifestError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PartialEncryption {
+    // Relative paths with '/', as in contents.json
+    pub unencrypted: Vec<String>,
+    // Error that stopped encryption
+    pub cause: Box<PackEncryptionError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOperation {
+    Read,
+    Write,
+    Encrypt,
+    Decrypt,
+    Minify,
+}
+
+impl Display for FileOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let operation = match self {
+            FileOperation::Read => "read",
+            FileOperation::Write => "write",
+            FileOperation::Encrypt => "encrypt",
+            FileOperation::Decrypt => "decrypt",
+            FileOperation::Minify => "minify",
+        };
+        write!(f, "{}", operation)
+    }
+}
+
+// Error of a single file in parallel processing
+#[derive(Debug)]
+pub struct FileError {
+    // Relative path with '/', as in contents.json
+    pub path: String,
+    pub operation: FileOperation,
+    pub source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl FileError {
+    pub fn new(
+        path: impl Into<String>,
+        operation: FileOperation,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            operation,
+            source: source.into(),
+        }
+    }
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Can't {} file {}", self.operation, self.path)
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+// Source is a trait object, so it's serialized as a message
+impl Serialize for FileError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FileError", 3)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("operation", &self.operation)?;
+        state.serialize_field("error", &error_chain(self.source.as_ref()))?;
+        state.end()
+    }
+}
+
+// Error with all its sources, like "Can't read file a.png: No such file or directory"
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
 // I'm manually creating and using write_all since for some
 // reason when I use std::fs::write sometimes it can write
 // zero bytes
@@ -67,97 +301,298 @@ pub fn list_relative_paths(root: &OsString) -> std::io::Result<Vec<PathBuf>> {
     Ok(entries)
 }
 
+// Shared flag to stop processing from another thread, e.g. on Ctrl-C.
+// Files that are already being processed are finished, the rest are skipped
+#[derive(Debug, Default, Clone)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ProcessingOptions {
+    // Number of worker threads, 0 means one per available core
+    pub jobs: usize,
+    // Stop after the first failed file instead of collecting all errors
+    pub fail_fast: bool,
+    pub cancellation: CancellationToken,
+}
+
+impl ProcessingOptions {
+    fn threads(&self) -> usize {
+        // Browsers don't let WebAssembly spawn threads
+        if cfg!(target_family = "wasm") {
+            return 1;
+        }
+        match self.jobs {
+            0 => thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            jobs => jobs,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParallelProcessingError<E> {
+    Failed(Vec<E>),
+    // Errors that happened before cancellation
+    Cancelled(Vec<E>),
+    // Panic message of a worker and errors collected before it
+    WorkerPanicked(String, Vec<E>),
+}
+
+impl From<ParallelProcessingError<FileError>> for PackEncryptionError {
+    fn from(e: ParallelProcessingError<FileError>) -> Self {
+        match e {
+            ParallelProcessingError::Failed(errors) => PackEncryptionError::ProcessingError(errors),
+            ParallelProcessingError::Cancelled(errors) => PackEncryptionError::Cancelled(errors),
+            ParallelProcessingError::WorkerPanicked(message, errors) => {
+                PackEncryptionError::WorkerPanicked(message, errors)
+            }
+        }
+    }
+}
+
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
 // Posted here to make code more readable
-type ParallelProcessingResult = Result<(), Vec<String>>;
+type ParallelProcessingResult<E> = Result<(), ParallelProcessingError<E>>;
+
+// State shared by everyone who processes tasks
+struct ProcessingState<'a, F> {
+    function: &'a F,
+    progress: &'a Option<(ProgressStage, ProgressCallback)>,
+    options: &'a ProcessingOptions,
+    processed: AtomicUsize,
+    // Set by the first error in fail fast mode
+    stop: AtomicBool,
+}
+
+impl<F> ProcessingState<'_, F> {
+    fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst) || self.options.cancellation.is_cancelled()
+    }
+
+    // Processes tasks until they end or processing is stopped, returns errors
+    // and panic message. Errors collected before a panic are kept
+    fn process<T, E>(&self, tasks: impl Iterator<Item = T>) -> (Vec<E>, Option<String>)
+    where
+        F: Fn(T) -> Result<(), E>,
+        T: ProgressItem,
+        E: std::error::Error,
+    {
+        let mut errors = Vec::new();
+        let panic = catch_unwind(AssertUnwindSafe(|| self.process_into(tasks, &mut errors)))
+            .err()
+            .map(|payload| panic_message(payload.as_ref()));
+        (errors, panic)
+    }
+
+    fn process_into<T, E>(&self, tasks: impl Iterator<Item = T>, errors: &mut Vec<E>)
+    where
+        F: Fn(T) -> Result<(), E>,
+        T: ProgressItem,
+        E: std::error::Error,
+    {
+        for task in tasks {
+            if self.is_stopped() {
+                break;
+            }
+
+            // Path is taken only when somebody listens
+            let path = self.progress.as_ref().map(|_| task.progress_path());
+            let result = (self.function)(task);
+            self.processed.fetch_add(1, Ordering::SeqCst);
+
+            if let (Some((stage, callback)), Some(path)) = (self.progress, path) {
+                callback.emit(match &result {
+                    Ok(()) => ProgressEvent::FileDone {
+                        stage: *stage,
+                        path,
+                    },
+                    Err(e) => ProgressEvent::FileFailed {
+                        stage: *stage,
+                        path,
+                        error: error_chain(e),
+                    },
+                });
+            }
+
+            if let Err(e) = result {
+                errors.push(e);
+                if self.options.fail_fast {
+                    self.stop.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+}
 
-// Simple parallel processing for encrypt/decrypt tasks. Maybe you can make it better
-pub fn parallel_processing<F, T>(tasks: Vec<T>, function: F) -> ParallelProcessingResult
+// Simple parallel processing for encrypt/decrypt tasks. Maybe you can make it better.
+// Every processed task is reported to progress callback, if there is one.
+// Workers are scoped threads, so function can borrow storage of the caller.
+// With one thread tasks are processed right on the calling one
+pub fn parallel_processing<F, T, E>(
+    tasks: Vec<T>,
+    function: F,
+    options: &ProcessingOptions,
+    progress: Option<(ProgressStage, ProgressCallback)>,
+) -> ParallelProcessingResult<E>
 where
-    F: Fn(T) -> Result<(), String> + Send + Sync + 'static,
-    T: Send + Sync + 'static + std::fmt::Debug,
+    F: Fn(T) -> Result<(), E> + Sync,
+    T: Send + std::fmt::Debug + ProgressItem,
+    E: std::error::Error + Send,
 {
     // For blazingly fast processing
-    use crossbeam_channel::unbounded;
+    use crossbeam_channel::bounded;
 
-    let available_threads = thread::available_parallelism()
-        .map(|n| n.get())
-        .unwrap_or(1);
+    let threads = options.threads();
 
-    let (sender, receiver) = unbounded();
-    let function_arc = Arc::new(function);
-    let mut handles = Vec::with_capacity(available_threads);
+    let total = tasks.len();
+    if let Some((stage, callback)) = &progress {
+        callback.emit(ProgressEvent::Started {
+            stage: *stage,
+            total,
+        });
+    }
 
-    // Producer thread
-    let producer = thread::spawn(move || {
-        for task in tasks {
-            sender.send(task).unwrap_or_default();
-        }
-    });
-
-    // Worker threads
-    for _ in 0..available_threads {
-        let receiver = receiver.clone();
-        /* Sorry for another clone */
-        let function = Arc::clone(&function_arc);
-        handles.push(thread::spawn(move || {
-            let mut errors = Vec::new();
-            while let Ok(task) = receiver.recv() {
-                if let Err(e) = function(task) {
-                    errors.push(e);
+    let state = ProcessingState {
+        function: &function,
+        progress: &progress,
+        options,
+        processed: AtomicUsize::new(0),
+        stop: AtomicBool::new(false),
+    };
+
+    let (errors, panic) = if threads == 1 {
+        // Panic is reported the same way as panic of a worker
+        state.process(tasks.into_iter())
+    } else {
+        // Bounded queue keeps workers busy without copying all tasks into channel
+        let (sender, receiver) = bounded::<T>(threads * 2);
+
+        thread::scope(|scope| {
+            let state = &state;
+            let handles = (0..threads)
+                .map(|_| {
+                    let receiver = receiver.clone();
+                    scope.spawn(move || state.process(receiver.into_iter()))
+                })
+                .collect::<Vec<_>>();
+            // Otherwise sending would block forever when all workers are gone
+            drop(receiver);
+
+            // Producer. Sending fails only when every worker has exited
+            for task in tasks {
+                if state.is_stopped() || sender.send(task).is_err() {
+                    break;
                 }
             }
-            errors
-        }));
-    }
+            drop(sender);
+
+            // Collecting errors. Panic in one worker doesn't stop the others,
+            // they finish the queue and the panic is reported after that.
+            // Every handle is joined, so scope itself never panics
+            let mut errors = Vec::new();
+            let mut panic = None;
+            for handle in handles {
+                // Workers catch panics themselves, so join fails only if that failed
+                let (worker_errors, worker_panic) = handle
+                    .join()
+                    .unwrap_or_else(|payload| (Vec::new(), Some(panic_message(payload.as_ref()))));
+                errors.extend(worker_errors);
+                panic = panic.or(worker_panic);
+            }
+            (errors, panic)
+        })
+    };
+    let processed = state.processed.load(Ordering::SeqCst);
 
-    let _ = producer.join();
+    if let Some((stage, callback)) = &progress {
+        callback.emit(ProgressEvent::Finished {
+            stage: *stage,
+            done: processed.saturating_sub(errors.len()),
+            failed: errors.len(),
+        });
+    }
 
-    // Collecting errors
-    let mut errors = Vec::new();
-    for handle in handles {
-        errors.extend(handle.join().unwrap());
+    if let Some(message) = panic {
+        return Err(ParallelProcessingError::WorkerPanicked(message, errors));
+    }
+    if options.cancellation.is_cancelled() && processed < total {
+        return Err(ParallelProcessingError::Cancelled(errors));
     }
 
     // These errors will fall directly on your head, that is, all at once =)
     if errors.is_empty() {
         Ok(())
     } else {
-        Err(errors)
+        Err(ParallelProcessingError::Failed(errors))
     }
 }
 
-pub fn parse_pack_encryption_args<F>(args: &[&str], command: F) -> Result<(), String>
-where
-    F: Fn(&str, OsString) -> Result<(), PackEncryptionError>,
-{
-    if args.is_empty() {
-        return Err(String::from(
-            "No arguments provided. Use 'help' to get list of all available commands.",
-        ));
-    }
-
-    // For cases then user somehow forgot to specify path
-    let path_arg = if args[1..].is_empty() {
-        let mut input = String::new();
-        get_input(
-            "It looks like you forgot that you need to specify the folder path. Enter path:",
-            &mut input,
-        );
-        input
-    } else {
-        // Sometimes paths can be with whitespaces, so we also handle that
-        args[1..].join(" ")
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let key = if args[0] == "-r" {
-        &generate_random_key()
-    } else {
-        args[0]
-    };
-    let path = OsString::from(path_arg);
+    // Fails on .bad files and panics on .panic ones
+    fn process_file(path: PathBuf) -> Result<(), FileError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bad") => Err(FileError::new(
+                path.to_string_lossy(),
+                FileOperation::Read,
+                "broken",
+            )),
+            Some("panic") => panic!("worker panicked on {}", path.display()),
+            _ => Ok(()),
+        }
+    }
 
-    (command)(key, path).map_err(|e| format!("Pack encryption error: {:#?}", e))?;
+    #[test]
+    fn errors_before_panic_are_kept() {
+        let tasks = ["a.bad", "b.png", "c.bad", "d.panic", "e.bad"]
+            .map(PathBuf::from)
+            .to_vec();
 
-    println!("Key: {:?}", key);
-    Ok(())
+        for jobs in [1, 2] {
+            let options = ProcessingOptions {
+                jobs,
+                ..Default::default()
+            };
+            match parallel_processing(tasks.clone(), process_file, &options, None) {
+                Err(ParallelProcessingError::WorkerPanicked(message, errors)) => {
+                    assert_eq!(message, "worker panicked on d.panic");
+                    let mut paths = errors.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+                    paths.sort();
+                    if jobs == 1 {
+                        assert_eq!(paths, ["a.bad", "c.bad"]);
+                    } else {
+                        // Other worker finishes the queue
+                        assert_eq!(paths, ["a.bad", "c.bad", "e.bad"]);
+                    }
+                }
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
 }
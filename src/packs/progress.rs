@@ -0,0 +1,93 @@
+use crate::packs::contents::ContentsRootItem;
+use crossbeam_channel::{unbounded, Receiver};
+use serde::Serialize;
+use std::fmt::{Debug, Formatter};
+use std::path::{PathBuf, MAIN_SEPARATOR};
+use std::sync::Arc;
+
+// Progress events of parallel processing. Library is silent by default,
+// CLI renders them as a progress bar
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressStage {
+    Minify,
+    Encrypt,
+    Decrypt,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    Started {
+        stage: ProgressStage,
+        total: usize,
+    },
+    FileDone {
+        stage: ProgressStage,
+        path: String,
+    },
+    FileFailed {
+        stage: ProgressStage,
+        path: String,
+        error: String,
+    },
+    Finished {
+        stage: ProgressStage,
+        done: usize,
+        failed: usize,
+    },
+}
+
+// Called from worker threads, so it must be cheap and thread safe
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(&ProgressEvent) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(&ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    // For those who prefer to receive events on their own thread
+    pub fn channel() -> (Self, Receiver<ProgressEvent>) {
+        let (sender, receiver) = unbounded();
+        let callback = Self::new(move |event| {
+            // Nobody listens anymore, that's fine
+            let _ = sender.send(event.clone());
+        });
+        (callback, receiver)
+    }
+
+    pub fn emit(&self, event: ProgressEvent) {
+        (self.0)(&event);
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProgressCallback")
+    }
+}
+
+// Tasks of parallel processing tell their path for progress events
+pub trait ProgressItem {
+    fn progress_path(&self) -> String;
+}
+
+impl ProgressItem for ContentsRootItem {
+    fn progress_path(&self) -> String {
+        self.path.clone()
+    }
+}
+
+impl<T: ProgressItem> ProgressItem for &T {
+    fn progress_path(&self) -> String {
+        (*self).progress_path()
+    }
+}
+
+impl ProgressItem for PathBuf {
+    fn progress_path(&self) -> String {
+        self.to_string_lossy().replace(MAIN_SEPARATOR, "/")
+    }
+}
@@ -0,0 +1,467 @@
+use crate::packs::pack_encryption::{list_relative_paths, write_file, PackEncryptionError};
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, metadata, read, remove_file, File};
+use std::io::{Cursor, Error, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR, MAIN_SEPARATOR_STR};
+use std::sync::RwLock;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// Storage is where pack files live: folder on disk, zip archive or memory.
+// Encryption works the same way with all of them. Paths are relative to the
+// pack root and have the format of list_relative_paths: OS separators,
+// and folders end with separator
+
+pub trait PackStorage: Send + Sync {
+    // All files and folders of the pack
+    fn list_entries(&self) -> std::io::Result<Vec<PathBuf>>;
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>>;
+    // Parent folders are created when needed
+    fn write(&self, path: &Path, bytes: &[u8]) -> std::io::Result<()>;
+    // Removes a file, folders are left as is
+    fn remove(&self, path: &Path) -> std::io::Result<()>;
+    // True for both files and folders
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn size(&self, path: &Path) -> std::io::Result<u64>;
+
+    // Up to length first bytes, e.g. to check file signature
+    fn read_prefix(&self, path: &Path, length: usize) -> std::io::Result<Vec<u8>> {
+        let mut bytes = self.read(path)?;
+        bytes.truncate(length);
+        Ok(bytes)
+    }
+
+    // Folder on disk, if storage is backed by one. Symlinks can only be there
+    fn local_path(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+// Entry name with '/' and without trailing one, as in zip archives
+fn entry_name(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace(MAIN_SEPARATOR, "/")
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("{} doesn't exist", path.display()),
+    )
+}
+
+pub struct DirectoryStorage {
+    root: PathBuf,
+}
+
+impl DirectoryStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl PackStorage for DirectoryStorage {
+    fn list_entries(&self) -> std::io::Result<Vec<PathBuf>> {
+        list_relative_paths(&self.root.as_os_str().to_os_string())
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        read(self.root.join(path))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        let full_path = self.root.join(path);
+        match write_file(bytes, &full_path) {
+            // Files are mostly written in place, so folders are created only when missing
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if let Some(parent) = full_path.parent() {
+                    create_dir_all(parent)?;
+                }
+                write_file(bytes, &full_path)
+            }
+            result => result,
+        }
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        remove_file(self.root.join(path))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.root.join(path).exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.root.join(path).is_dir()
+    }
+
+    fn size(&self, path: &Path) -> std::io::Result<u64> {
+        metadata(self.root.join(path)).map(|metadata| metadata.len())
+    }
+
+    fn read_prefix(&self, path: &Path, length: usize) -> std::io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(length);
+        File::open(self.root.join(path))?
+            .take(length as u64)
+            .read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn local_path(&self) -> Option<PathBuf> {
+        Some(self.root.clone())
+    }
+}
+
+// Files by their names with '/'. Folders exist as long as they have files,
+// empty ones are kept as names ending with '/'
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    files: RwLock<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_files(self) -> BTreeMap<String, Vec<u8>> {
+        self.files.into_inner().unwrap()
+    }
+}
+
+impl From<BTreeMap<String, Vec<u8>>> for MemoryStorage {
+    fn from(files: BTreeMap<String, Vec<u8>>) -> Self {
+        Self {
+            files: RwLock::new(files),
+        }
+    }
+}
+
+impl PackStorage for MemoryStorage {
+    fn list_entries(&self) -> std::io::Result<Vec<PathBuf>> {
+        let files = self.files.read().unwrap();
+
+        let mut folders = files
+            .keys()
+            .flat_map(|name| name.match_indices('/').map(|(index, _)| &name[..=index]))
+            .collect::<Vec<_>>();
+        folders.sort();
+        folders.dedup();
+
+        Ok(folders
+            .into_iter()
+            .chain(
+                files
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|name| !name.ends_with('/')),
+            )
+            .map(|name| PathBuf::from(name.replace('/', MAIN_SEPARATOR_STR)))
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&entry_name(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        let name = entry_name(path);
+        if self.is_dir(path) {
+            return Err(Error::new(
+                ErrorKind::IsADirectory,
+                format!("{} is a folder", name),
+            ));
+        }
+        self.files.write().unwrap().insert(name, bytes.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.files
+            .write()
+            .unwrap()
+            .remove(&entry_name(path))
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.read().unwrap().contains_key(&entry_name(path)) || self.is_dir(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let prefix = format!("{}/", entry_name(path));
+        self.files
+            .read()
+            .unwrap()
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(name, _)| name.starts_with(&prefix))
+    }
+
+    fn size(&self, path: &Path) -> std::io::Result<u64> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&entry_name(path))
+            .map(|bytes| bytes.len() as u64)
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+// Unpacked archive is kept in memory, so archives that unpack
+// into more than this, like zip bombs, are refused
+pub const MAX_UNPACKED_SIZE: u64 = 2 << 30;
+
+// Archive is read into memory on open and written back only by save,
+// so failed encryption never leaves broken archive
+#[derive(Debug)]
+pub struct ZipStorage {
+    files: MemoryStorage,
+}
+
+impl ZipStorage {
+    pub fn open(path: &Path) -> Result<Self, PackEncryptionError> {
+        let archive_file = File::open(path).map_err(PackEncryptionError::FileSystemError)?;
+        Self::read_archive(archive_file, MAX_UNPACKED_SIZE)
+    }
+
+    // Archive that is already in memory, e.g. received over network
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PackEncryptionError> {
+        Self::read_archive(Cursor::new(bytes), MAX_UNPACKED_SIZE)
+    }
+
+    fn read_archive(reader: impl Read + Seek, limit: u64) -> Result<Self, PackEncryptionError> {
+        let mut archive = ZipArchive::new(reader).map_err(PackEncryptionError::ArchiveError)?;
+        let mut remaining = limit;
+
+        let mut files = BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(PackEncryptionError::ArchiveError)?;
+            // Entries like ../file or /file would escape the pack once extracted
+            if file.enclosed_name().is_none() {
+                return Err(PackEncryptionError::ArchiveError(ZipError::InvalidArchive(
+                    format!("Entry {} leads outside of the archive", file.name()).into(),
+                )));
+            }
+
+            let name = file.name().replace('\\', "/");
+            // Sizes in the header can be anything, so only actually read bytes are counted
+            let mut bytes = Vec::new();
+            if !file.is_dir() {
+                (&mut file)
+                    .take(remaining + 1)
+                    .read_to_end(&mut bytes)
+                    .map_err(PackEncryptionError::FileSystemError)?;
+            }
+            remaining = remaining.checked_sub(bytes.len() as u64).ok_or_else(|| {
+                PackEncryptionError::ArchiveError(ZipError::InvalidArchive(
+                    format!("Archive is larger than {} bytes when unpacked", limit).into(),
+                ))
+            })?;
+            files.insert(name, bytes);
+        }
+
+        Ok(Self {
+            files: MemoryStorage::from(files),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), PackEncryptionError> {
+        write_archive(self, path)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PackEncryptionError> {
+        Ok(write_zip(self, Cursor::new(Vec::new()))?.into_inner())
+    }
+}
+
+impl PackStorage for ZipStorage {
+    fn list_entries(&self) -> std::io::Result<Vec<PathBuf>> {
+        self.files.list_entries()
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.files.read(path)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        self.files.write(path, bytes)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.files.remove(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.is_dir(path)
+    }
+
+    fn size(&self, path: &Path) -> std::io::Result<u64> {
+        self.files.size(path)
+    }
+}
+
+// Pack inside another storage, e.g. one of packs of .mcaddon
+pub struct SubfolderStorage<'a> {
+    storage: &'a dyn PackStorage,
+    prefix: PathBuf,
+}
+
+impl<'a> SubfolderStorage<'a> {
+    pub fn new(storage: &'a dyn PackStorage, prefix: impl Into<PathBuf>) -> Self {
+        Self {
+            storage,
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl PackStorage for SubfolderStorage<'_> {
+    fn list_entries(&self) -> std::io::Result<Vec<PathBuf>> {
+        // Compared as strings, since Path drops trailing separator of folders
+        let prefix = self.prefix.to_string_lossy();
+        let prefix = if prefix.is_empty() || prefix.ends_with(MAIN_SEPARATOR) {
+            prefix.into_owned()
+        } else {
+            format!("{}{}", prefix, MAIN_SEPARATOR)
+        };
+
+        Ok(self
+            .storage
+            .list_entries()?
+            .into_iter()
+            .filter_map(|path| {
+                path.to_string_lossy()
+                    .strip_prefix(&prefix)
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from)
+            })
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> std::io::Result<Vec<u8>> {
+        self.storage.read(&self.prefix.join(path))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        self.storage.write(&self.prefix.join(path), bytes)
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        self.storage.remove(&self.prefix.join(path))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.storage.exists(&self.prefix.join(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.storage.is_dir(&self.prefix.join(path))
+    }
+
+    fn size(&self, path: &Path) -> std::io::Result<u64> {
+        self.storage.size(&self.prefix.join(path))
+    }
+
+    fn read_prefix(&self, path: &Path, length: usize) -> std::io::Result<Vec<u8>> {
+        self.storage.read_prefix(&self.prefix.join(path), length)
+    }
+
+    fn local_path(&self) -> Option<PathBuf> {
+        self.storage
+            .local_path()
+            .map(|root| root.join(&self.prefix))
+    }
+}
+
+pub(crate) fn write_archive(
+    storage: &dyn PackStorage,
+    output_path: &Path,
+) -> Result<(), PackEncryptionError> {
+    let output_file = File::create(output_path).map_err(PackEncryptionError::FileSystemError)?;
+    write_zip(storage, output_file)?;
+    Ok(())
+}
+
+fn write_zip<W: Write + Seek>(
+    storage: &dyn PackStorage,
+    output: W,
+) -> Result<W, PackEncryptionError> {
+    let relative_paths = storage
+        .list_entries()
+        .map_err(PackEncryptionError::FileSystemError)?;
+
+    let mut writer = ZipWriter::new(output);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for rel_path in relative_paths {
+        // Zip entries always use '/'
+        let name = rel_path.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+
+        if name.ends_with('/') {
+            writer
+                .add_directory(name, options)
+                .map_err(PackEncryptionError::ArchiveError)?;
+            continue;
+        }
+
+        let bytes = storage
+            .read(&rel_path)
+            .map_err(PackEncryptionError::FileSystemError)?;
+        writer
+            .start_file(name, options)
+            .map_err(PackEncryptionError::ArchiveError)?;
+        writer
+            .write_all(&bytes)
+            .map_err(PackEncryptionError::FileSystemError)?;
+    }
+
+    writer.finish().map_err(PackEncryptionError::ArchiveError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, usize)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, size) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&vec![0; *size]).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn unpacked_size_is_limited() {
+        let bytes = archive(&[("a.json", 600), ("b.json", 400)]);
+        assert!(ZipStorage::read_archive(Cursor::new(&bytes), 1000).is_ok());
+        assert!(matches!(
+            ZipStorage::read_archive(Cursor::new(&bytes), 999),
+            Err(PackEncryptionError::ArchiveError(_))
+        ));
+        // Single file above the limit is stopped while reading
+        assert!(matches!(
+            ZipStorage::read_archive(Cursor::new(&archive(&[("a.json", 5000)])), 1000),
+            Err(PackEncryptionError::ArchiveError(_))
+        ));
+    }
+}
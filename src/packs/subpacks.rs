@@ -0,0 +1,77 @@
+use crate::packs::contents::ContentsRootItem;
+use crate::packs::manifest::Manifest;
+use crate::packs::storage::PackStorage;
+use serde::Serialize;
+use std::path::Path;
+
+// Subpacks are memory tier variants of resource pack, that live in
+// subpacks/<folder_name>/ and are listed in the manifest
+
+#[derive(Debug, Serialize)]
+pub struct SubpackStats {
+    pub folder_name: String,
+    pub name: String,
+    pub memory_tier: Option<u32>,
+    pub files: usize,
+    // Files that got a key in contents.json
+    pub encrypted_files: usize,
+    pub bytes: u64,
+}
+
+// Every subpack from manifest must have its folder, otherwise
+// Minecraft will show empty tier in pack settings
+pub fn validate_subpack_folders(
+    storage: &dyn PackStorage,
+    manifest: &Manifest,
+) -> Result<(), String> {
+    let missing = manifest
+        .subpacks
+        .iter()
+        .filter(|subpack| !storage.is_dir(&Path::new("subpacks").join(&subpack.folder_name)))
+        .map(|subpack| subpack.folder_name.as_str())
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Subpack folders listed in manifest.json don't exist: {}",
+            missing.join(", ")
+        ))
+    }
+}
+
+pub fn collect_subpack_stats(
+    storage: &dyn PackStorage,
+    manifest: &Manifest,
+    content: &[ContentsRootItem],
+) -> Vec<SubpackStats> {
+    manifest
+        .subpacks
+        .iter()
+        .map(|subpack| {
+            let prefix = format!("subpacks/{}/", subpack.folder_name);
+            let mut stats = SubpackStats {
+                folder_name: subpack.folder_name.clone(),
+                name: subpack.name.clone(),
+                memory_tier: subpack.memory_tier.or(subpack.memory_performance_tier),
+                files: 0,
+                encrypted_files: 0,
+                bytes: 0,
+            };
+
+            // Folders also have entries in contents.json, we don't count them
+            for item in content
+                .iter()
+                .filter(|item| item.path.starts_with(&prefix) && !item.path.ends_with('/'))
+            {
+                stats.files += 1;
+                if item.key.is_some() {
+                    stats.encrypted_files += 1;
+                }
+                stats.bytes += storage.size(Path::new(&item.path)).unwrap_or_default();
+            }
+            stats
+        })
+        .collect()
+}
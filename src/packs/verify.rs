@@ -0,0 +1,99 @@
+use crate::packs::contents::{find_unsafe_paths, UnsafePath};
+use crate::packs::encryption::decrypt::decrypt_contents;
+use crate::packs::manifest::read_manifest;
+use crate::packs::pack_encryption::{
+    list_relative_paths, FileError, FileOperation, PackEncryptionError,
+};
+use crate::packs::storage::DirectoryStorage;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs::read;
+use std::path::{Path, MAIN_SEPARATOR};
+
+// Verify checks encrypted pack with its key without changing anything
+
+#[derive(Debug, Serialize)]
+pub struct VerifyReport {
+    // UUID from contents.json header
+    pub uuid: String,
+    pub manifest_uuid: Option<String>,
+    // Files and folders listed in contents.json
+    pub entries: usize,
+    // Listed in contents.json, but don't exist
+    pub missing: Vec<String>,
+    // Exist in the pack, but aren't listed in contents.json
+    pub unlisted: Vec<String>,
+    // Listed in contents.json, but lead outside of the pack
+    pub unsafe_paths: Vec<UnsafePath>,
+}
+
+impl VerifyReport {
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty()
+            && self.unlisted.is_empty()
+            && self.unsafe_paths.is_empty()
+            && self
+                .manifest_uuid
+                .as_ref()
+                .is_some_and(|uuid| *uuid == self.uuid)
+    }
+}
+
+pub fn verify(key: &str, target_path: &OsString) -> Result<VerifyReport, PackEncryptionError> {
+    let root = Path::new(target_path);
+    let contents_file_content = read(root.join("contents.json")).map_err(|e| {
+        PackEncryptionError::FileError(FileError::new("contents.json", FileOperation::Read, e))
+    })?;
+
+    // Wrong key fails here, since contents.json won't be valid json
+    let (header, contents_root, _) = decrypt_contents(key, &contents_file_content)?;
+
+    let unsafe_paths = find_unsafe_paths(&DirectoryStorage::new(root), &contents_root.content)
+        .map_err(PackEncryptionError::FileSystemError)?;
+
+    // Unsafe paths are reported only once, not as missing too
+    let listed = contents_root
+        .content
+        .iter()
+        .map(|item| item.path.as_str())
+        // Packs encrypted again after decryption may list contents.json itself
+        .filter(|path| *path != "contents.json")
+        .filter(|path| {
+            !unsafe_paths
+                .iter()
+                .any(|unsafe_path| unsafe_path.path == *path)
+        })
+        .collect::<HashSet<_>>();
+
+    let existing = list_relative_paths(target_path)
+        .map_err(PackEncryptionError::FileSystemError)?
+        .into_iter()
+        .map(|path| path.to_string_lossy().replace(MAIN_SEPARATOR, "/"))
+        .filter(|path| path != "contents.json")
+        .collect::<HashSet<_>>();
+
+    let mut missing = listed
+        .iter()
+        .filter(|path| !existing.contains(**path))
+        .map(|path| path.to_string())
+        .collect::<Vec<_>>();
+    let mut unlisted = existing
+        .iter()
+        .filter(|path| !listed.contains(path.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    missing.sort();
+    unlisted.sort();
+
+    Ok(VerifyReport {
+        uuid: header.uuid,
+        manifest_uuid: read_manifest(target_path)
+            .ok()
+            .map(|manifest| manifest.header.uuid),
+        entries: contents_root.content.len(),
+        missing,
+        unlisted,
+        unsafe_paths,
+    })
+}
@@ -0,0 +1,507 @@
+use crate::packs::contents::{generate_contents_root, ContentsRoot, ContentsRootItem};
+use crate::packs::encryption::encrypt::generate_contents_file;
+use crate::packs::manifest::read_manifest;
+use crate::packs::pack_encryption::{
+    list_relative_paths, parallel_processing, write_file, FileError, FileOperation,
+    PackEncryptionError, ProcessingOptions,
+};
+use crate::packs::progress::{ProgressCallback, ProgressStage};
+use crate::utils::cipher::aes256_cbf8_encrypt;
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir_all, read, remove_dir, remove_file};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Watch mode keeps encrypted copy of the pack up to date while it's edited.
+// Files keep their keys between updates, so only changed files are
+// re-encrypted, and contents.json is regenerated after every update.
+// IV is taken from the key, so changed file is encrypted with the same
+// key and IV as before. That's fine for dev builds on a test server,
+// release builds must be made by encrypt or build
+
+// Editors save files in several steps, so events are collected
+// until there is a pause
+const DEBOUNCE: Duration = Duration::from_millis(300);
+// How often cancellation is checked while nothing happens
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Default, Clone)]
+pub struct WatchOptions {
+    // Cancellation stops watching
+    pub processing: ProcessingOptions,
+    pub progress: Option<ProgressCallback>,
+}
+
+#[derive(Debug)]
+pub enum WatchError {
+    NotifyError(notify::Error),
+    EncryptionError(PackEncryptionError),
+}
+
+impl Display for WatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchError::NotifyError(_) => write!(f, "Can't watch for file changes"),
+            // Encryption errors already say what went wrong
+            WatchError::EncryptionError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for WatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WatchError::NotifyError(e) => Some(e),
+            WatchError::EncryptionError(e) => e.source(),
+        }
+    }
+}
+
+impl From<notify::Error> for WatchError {
+    fn from(e: notify::Error) -> Self {
+        WatchError::NotifyError(e)
+    }
+}
+
+impl From<PackEncryptionError> for WatchError {
+    fn from(e: PackEncryptionError) -> Self {
+        WatchError::EncryptionError(e)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatchUpdate {
+    pub uuid: String,
+    // Files written to output, encrypted or copied as is
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    // Files and folders listed in contents.json
+    pub entries: usize,
+}
+
+// Paths are compared after canonicalization, see absolute_path
+fn check_folders(source: &Path, output: &Path) -> Result<(), PackEncryptionError> {
+    if output.starts_with(source) || source.starts_with(output) {
+        return Err(PackEncryptionError::DataCollectionError(String::from(
+            "Output folder can't be inside the source folder or contain it",
+        )));
+    }
+    Ok(())
+}
+
+// Canonical path of a folder that may not exist yet: its closest existing
+// parent is canonicalized and the rest is appended
+fn absolute_path(path: &Path) -> std::io::Result<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+    // Relative path without existing parts is relative to current folder
+    let existing = if existing.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        existing
+    };
+
+    let mut absolute = existing.canonicalize()?;
+    absolute.extend(rest.iter().rev());
+    Ok(absolute)
+}
+
+// Checks folders before anything is created, returns canonical paths of both
+fn prepare_folders(
+    source: &Path,
+    output: &Path,
+) -> Result<(PathBuf, PathBuf), PackEncryptionError> {
+    let source = source
+        .canonicalize()
+        .map_err(PackEncryptionError::FileSystemError)?;
+    let output = absolute_path(output).map_err(PackEncryptionError::FileSystemError)?;
+    check_folders(&source, &output)?;
+
+    create_dir_all(&output).map_err(PackEncryptionError::FileSystemError)?;
+    Ok((source, output))
+}
+
+fn to_relative(source: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(source)
+        .ok()
+        .map(|path| path.to_string_lossy().replace(MAIN_SEPARATOR, "/"))
+}
+
+// Brings output in sync with source. Without changed paths every file is written
+fn sync(
+    source: &Path,
+    output: &Path,
+    key: &str,
+    keys: &mut HashMap<String, Option<String>>,
+    changed: Option<&HashSet<String>>,
+    options: &WatchOptions,
+) -> Result<WatchUpdate, PackEncryptionError> {
+    let manifest = read_manifest(&source.as_os_str().to_os_string())
+        .map_err(PackEncryptionError::ManifestError)?;
+    manifest
+        .validate()
+        .map_err(PackEncryptionError::ManifestError)?;
+
+    // Old contents.json of the source would overwrite generated one
+    let relative_paths = list_relative_paths(&source.as_os_str().to_os_string())
+        .map_err(PackEncryptionError::FileSystemError)?
+        .into_iter()
+        .filter(|path| path != Path::new("contents.json"))
+        .collect::<Vec<_>>();
+
+    // New files get new keys, the rest keep old ones
+    let content = generate_contents_root(&relative_paths)
+        .into_iter()
+        .map(|item| ContentsRootItem {
+            key: keys.get(&item.path).cloned().unwrap_or(item.key),
+            path: item.path,
+        })
+        .collect::<Vec<_>>();
+
+    let listed = content
+        .iter()
+        .map(|item| item.path.as_str())
+        .collect::<HashSet<_>>();
+    let mut removed = keys
+        .keys()
+        .filter(|path| !listed.contains(path.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    removed.sort();
+
+    // Only files written by watch are removed. Files go before their folders,
+    // and folders with files of somebody else are kept
+    for path in removed.iter().rev() {
+        let full_path = output.join(path);
+        let result = if path.ends_with('/') {
+            remove_dir(&full_path)
+        } else {
+            remove_file(&full_path)
+        };
+        match result {
+            Err(e)
+                if e.kind() != ErrorKind::NotFound && e.kind() != ErrorKind::DirectoryNotEmpty =>
+            {
+                return Err(PackEncryptionError::ProcessingError(vec![FileError::new(
+                    path.clone(),
+                    FileOperation::Write,
+                    e,
+                )]))
+            }
+            _ => {}
+        }
+    }
+
+    let tasks = content
+        .iter()
+        .filter(|item| !item.path.ends_with('/'))
+        .filter(|item| match changed {
+            Some(changed) => changed.contains(&item.path) || !keys.contains_key(&item.path),
+            None => true,
+        })
+        .map(|item| ContentsRootItem {
+            path: item.path.clone(),
+            key: item.key.clone(),
+        })
+        .collect::<Vec<_>>();
+    let mut updated = tasks
+        .iter()
+        .map(|item| item.path.clone())
+        .collect::<Vec<_>>();
+    updated.sort();
+
+    let (source_path, output_path) = (
+        Arc::new(source.to_path_buf()),
+        Arc::new(output.to_path_buf()),
+    );
+    let progress = options
+        .progress
+        .clone()
+        .map(|callback| (ProgressStage::Encrypt, callback));
+
+    parallel_processing(
+        tasks,
+        move |item| {
+            let path = &item.path;
+            let file_content = read(source_path.join(path))
+                .map_err(|e| FileError::new(path, FileOperation::Read, e))?;
+
+            // Files without key are copied as is
+            let file_content = match &item.key {
+                Some(key) => aes256_cbf8_encrypt(key, file_content)
+                    .map_err(|e| FileError::new(path, FileOperation::Encrypt, e))?,
+                None => file_content,
+            };
+
+            let full_path = output_path.join(path);
+            if let Some(parent) = full_path.parent() {
+                create_dir_all(parent)
+                    .map_err(|e| FileError::new(path, FileOperation::Write, e))?;
+            }
+            write_file(&file_content, &full_path)
+                .map_err(|e| FileError::new(path, FileOperation::Write, e))
+        },
+        &options.processing,
+        progress,
+    )
+    .map_err(PackEncryptionError::from)?;
+
+    let entries = content.len();
+    *keys = content
+        .iter()
+        .map(|item| (item.path.clone(), item.key.clone()))
+        .collect();
+
+    let root = ContentsRoot {
+        version: 1,
+        content,
+    };
+    let contents_file = generate_contents_file(key, &manifest.header.uuid, &root)?;
+    write_file(&contents_file, &output.join("contents.json")).map_err(|e| {
+        PackEncryptionError::FileError(FileError::new("contents.json", FileOperation::Write, e))
+    })?;
+
+    Ok(WatchUpdate {
+        uuid: manifest.header.uuid,
+        updated,
+        removed,
+        entries,
+    })
+}
+
+// Encrypted copy of source is written to output, then every change of source
+// is reported to on_update. Other files of output are left as is. Failed updates
+// don't stop watching, since pack may be broken in the middle of editing.
+// Returns after cancellation
+pub fn watch(
+    source: &Path,
+    output: &Path,
+    key: &str,
+    options: &WatchOptions,
+    mut on_update: impl FnMut(Result<WatchUpdate, WatchError>),
+) -> Result<(), WatchError> {
+    let (source, output) = prepare_folders(source, output)?;
+
+    // Watcher is started before the first sync, so changes made during it aren't lost
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(&source, RecursiveMode::Recursive)?;
+
+    let mut keys = HashMap::new();
+    // Changes of failed update would be lost, so after failure
+    // the whole pack is synced again
+    let mut synced = false;
+    let mut changed = HashSet::new();
+    let cancellation = &options.processing.cancellation;
+
+    loop {
+        if !synced || !changed.is_empty() {
+            let result = sync(
+                &source,
+                &output,
+                key,
+                &mut keys,
+                Some(&changed).filter(|_| synced),
+                options,
+            );
+            if cancellation.is_cancelled() {
+                return Ok(());
+            }
+            synced = result.is_ok();
+            changed.clear();
+            on_update(result.map_err(WatchError::from));
+        }
+
+        // Waiting for the first event, then collecting the rest until pause
+        let mut timeout = POLL_INTERVAL;
+        loop {
+            if cancellation.is_cancelled() {
+                return Ok(());
+            }
+
+            match receiver.recv_timeout(timeout) {
+                Ok(Ok(event)) => {
+                    if matches!(event.kind, EventKind::Access(_)) {
+                        continue;
+                    }
+                    changed.extend(
+                        event
+                            .paths
+                            .iter()
+                            .filter_map(|path| to_relative(&source, path)),
+                    );
+                    timeout = DEBOUNCE;
+                }
+                Ok(Err(e)) => on_update(Err(WatchError::from(e))),
+                Err(RecvTimeoutError::Timeout) if timeout == DEBOUNCE => break,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::encryption::decrypt::decrypt_contents;
+    use crate::packs::fixtures::{KEY, MANIFEST};
+    use crate::utils::cipher::aes256_cfb8_decrypt;
+    use std::fs;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    // Keys of files from contents.json of the output
+    fn output_keys(output: &Path) -> HashMap<String, Option<String>> {
+        let contents = fs::read(output.join("contents.json")).unwrap();
+        let (_, root, _) = decrypt_contents(KEY, &contents).unwrap();
+        root.content
+            .into_iter()
+            .map(|item| (item.path, item.key))
+            .collect()
+    }
+
+    fn decrypted(output: &Path, path: &str, key: &Option<String>) -> String {
+        let content = fs::read(output.join(path)).unwrap();
+        let key = key.as_deref().unwrap();
+        String::from_utf8(aes256_cfb8_decrypt(key, content).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn sync_updates_output() {
+        let source = tempfile::TempDir::new().unwrap();
+        let output = tempfile::TempDir::new().unwrap();
+        let (source, output) = (source.path(), output.path());
+        write(source, "manifest.json", MANIFEST);
+        write(source, "entity/a.json", "a");
+        write(source, "entity/b.json", "b");
+        write(source, "removed/c.json", "c");
+
+        let mut keys = HashMap::new();
+        let options = WatchOptions::default();
+        let first = sync(source, output, KEY, &mut keys, None, &options).unwrap();
+        assert_eq!(
+            first.updated,
+            [
+                "entity/a.json",
+                "entity/b.json",
+                "manifest.json",
+                "removed/c.json"
+            ]
+        );
+        assert!(first.removed.is_empty());
+        assert_eq!(output_keys(output), keys);
+        let first_keys = keys.clone();
+        assert_eq!(
+            decrypted(output, "entity/a.json", &keys["entity/a.json"]),
+            "a"
+        );
+        // Manifest is copied as is
+        assert_eq!(
+            fs::read_to_string(output.join("manifest.json")).unwrap(),
+            MANIFEST
+        );
+
+        write(source, "entity/a.json", "changed a");
+        write(source, "entity/d.json", "d");
+        fs::remove_dir_all(source.join("removed")).unwrap();
+        write(output, "notes.txt", "not written by watch");
+        let changed = [
+            "entity/a.json",
+            "entity/d.json",
+            "removed/c.json",
+            "removed",
+        ]
+        .map(String::from)
+        .into_iter()
+        .collect::<HashSet<_>>();
+        let update = sync(source, output, KEY, &mut keys, Some(&changed), &options).unwrap();
+
+        assert_eq!(update.updated, ["entity/a.json", "entity/d.json"]);
+        assert_eq!(update.removed, ["removed/", "removed/c.json"]);
+        assert!(!output.join("removed").exists());
+        assert!(output.join("notes.txt").exists());
+        assert_eq!(update.entries, 5);
+
+        // Changed and untouched files keep their keys, new ones get new keys
+        let output_keys = output_keys(output);
+        assert_eq!(output_keys, keys);
+        for path in ["entity/a.json", "entity/b.json"] {
+            assert_eq!(keys[path], first_keys[path], "{}", path);
+        }
+        assert!(!first_keys.contains_key("entity/d.json"));
+        assert_eq!(
+            decrypted(output, "entity/a.json", &keys["entity/a.json"]),
+            "changed a"
+        );
+        assert_eq!(
+            decrypted(output, "entity/b.json", &keys["entity/b.json"]),
+            "b"
+        );
+        assert_eq!(
+            decrypted(output, "entity/d.json", &keys["entity/d.json"]),
+            "d"
+        );
+    }
+
+    #[test]
+    fn removed_folder_with_other_files_is_kept() {
+        let source = tempfile::TempDir::new().unwrap();
+        let output = tempfile::TempDir::new().unwrap();
+        let (source, output) = (source.path(), output.path());
+        write(source, "manifest.json", MANIFEST);
+        write(source, "sounds/a.ogg", "a");
+
+        let mut keys = HashMap::new();
+        let options = WatchOptions::default();
+        sync(source, output, KEY, &mut keys, None, &options).unwrap();
+
+        write(output, "sounds/readme.txt", "not written by watch");
+        fs::remove_dir_all(source.join("sounds")).unwrap();
+        let changed = HashSet::from([String::from("sounds")]);
+        let update = sync(source, output, KEY, &mut keys, Some(&changed), &options).unwrap();
+
+        assert_eq!(update.removed, ["sounds/", "sounds/a.ogg"]);
+        assert!(!output.join("sounds/a.ogg").exists());
+        assert!(output.join("sounds/readme.txt").exists());
+    }
+
+    #[test]
+    fn output_inside_source() {
+        let source = Path::new("/packs/rp");
+        assert!(check_folders(source, Path::new("/packs/rp/build")).is_err());
+        assert!(check_folders(source, Path::new("/packs")).is_err());
+        assert!(check_folders(source, Path::new("/packs/rp_encrypted")).is_ok());
+
+        // Invalid output isn't created
+        let source = tempfile::TempDir::new().unwrap();
+        let output = source.path().join("build/encrypted");
+        assert!(prepare_folders(source.path(), &output).is_err());
+        assert!(!source.path().join("build").exists());
+
+        let output = tempfile::TempDir::new().unwrap();
+        let new_output = output.path().join("new/encrypted");
+        let (_, prepared) = prepare_folders(source.path(), &new_output).unwrap();
+        assert!(new_output.is_dir());
+        assert_eq!(prepared, new_output.canonicalize().unwrap());
+    }
+}
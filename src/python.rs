@@ -0,0 +1,295 @@
+// Python module for asset pipelines, built with maturin (see pyproject.toml).
+// Reports are returned as dicts with the same fields as json output of the CLI,
+// and encryption runs without holding the GIL
+
+use crate::packs::archive::encrypt_archive as encrypt_archive_file;
+use crate::packs::encryption::decrypt::{decrypt as decrypt_dir, decrypt_storage, DecryptOptions};
+use crate::packs::encryption::encrypt::{encrypt as encrypt_dir, encrypt_storage, EncryptOptions};
+use crate::packs::encryption::memory::normalize_paths;
+use crate::packs::inspect::inspect as inspect_dir;
+use crate::packs::pack_encryption::{error_chain, PackEncryptionError, ProcessingOptions};
+use crate::packs::storage::MemoryStorage;
+use crate::packs::verify::verify as verify_dir;
+use crate::utils::cipher::generate_random_key;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+create_exception!(kotik, KotikError, PyException, "Base class of kotik errors");
+create_exception!(
+    kotik,
+    InvalidKeyError,
+    KotikError,
+    "Key isn't 32 bytes long"
+);
+create_exception!(
+    kotik,
+    ManifestError,
+    KotikError,
+    "manifest.json is missing or invalid"
+);
+create_exception!(
+    kotik,
+    AlreadyEncryptedError,
+    KotikError,
+    "Pack looks already encrypted, force=True encrypts it anyway"
+);
+create_exception!(
+    kotik,
+    UnsafePathsError,
+    KotikError,
+    "contents.json lists paths outside of the pack"
+);
+create_exception!(
+    kotik,
+    ContentsError,
+    KotikError,
+    "contents.json can't be read, probably key is wrong"
+);
+create_exception!(kotik, ArchiveError, KotikError, "Invalid zip archive");
+create_exception!(kotik, LintError, KotikError, "Pack linter found errors");
+create_exception!(
+    kotik,
+    ProcessingError,
+    KotikError,
+    "Some files failed to encrypt or decrypt"
+);
+
+// Same fields as json output, since serde_json is already there
+fn to_python<'py>(py: Python<'py>, value: &impl Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let text = serde_json::to_string(value).map_err(|e| KotikError::new_err(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (text,))
+}
+
+// Exception gets details attribute, e.g. list of failed files
+impl From<PackEncryptionError> for PyErr {
+    fn from(error: PackEncryptionError) -> Self {
+        let message = error_chain(&error);
+        let exception = match &error {
+            PackEncryptionError::CipherError(_) => InvalidKeyError::new_err(message),
+            PackEncryptionError::ManifestError(_) => ManifestError::new_err(message),
+            PackEncryptionError::AlreadyEncrypted(_) => AlreadyEncryptedError::new_err(message),
+            PackEncryptionError::UnsafePaths(_) => UnsafePathsError::new_err(message),
+            PackEncryptionError::ContentsDecodingError(_) | PackEncryptionError::JsonError(_) => {
+                ContentsError::new_err(message)
+            }
+            PackEncryptionError::ArchiveError(_) => ArchiveError::new_err(message),
+            PackEncryptionError::LintError(_) => LintError::new_err(message),
+            PackEncryptionError::ProcessingError(_)
+            | PackEncryptionError::Cancelled(_)
+            | PackEncryptionError::PartiallyEncrypted(_)
+            | PackEncryptionError::WorkerPanicked(..) => ProcessingError::new_err(message),
+            _ => KotikError::new_err(message),
+        };
+
+        Python::attach(|py| {
+            if let Ok(details) = to_python(py, &error.details()) {
+                let _ = exception.value(py).setattr("details", details);
+            }
+        });
+        exception
+    }
+}
+
+// Checked before anything is read, so wrong key length never looks like broken pack
+fn check_key(key: &str) -> PyResult<()> {
+    if key.len() != 32 {
+        return Err(InvalidKeyError::new_err(format!(
+            "Key must be 32 bytes long, got {}",
+            key.len()
+        )));
+    }
+    Ok(())
+}
+
+fn encrypt_options(
+    minify: bool,
+    lint: bool,
+    strict_json: bool,
+    force: bool,
+    jobs: usize,
+) -> EncryptOptions {
+    EncryptOptions {
+        lint,
+        minify,
+        strict_json,
+        force,
+        processing: ProcessingOptions {
+            jobs,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn decrypt_options(jobs: usize) -> DecryptOptions {
+    DecryptOptions {
+        processing: ProcessingOptions {
+            jobs,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn to_bytes_dict<'py>(
+    py: Python<'py>,
+    files: BTreeMap<String, Vec<u8>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    for (path, bytes) in files {
+        dict.set_item(path, PyBytes::new(py, &bytes))?;
+    }
+    Ok(dict)
+}
+
+/// Random 32 characters key
+#[pyfunction]
+fn random_key() -> String {
+    generate_random_key()
+}
+
+/// Encrypts pack folder in place and returns report
+#[pyfunction]
+#[pyo3(signature = (path, key, *, minify=false, lint=true, strict_json=false, force=false, jobs=0))]
+#[allow(clippy::too_many_arguments)]
+fn encrypt<'py>(
+    py: Python<'py>,
+    path: PathBuf,
+    key: String,
+    minify: bool,
+    lint: bool,
+    strict_json: bool,
+    force: bool,
+    jobs: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    check_key(&key)?;
+    let options = encrypt_options(minify, lint, strict_json, force, jobs);
+    let report = py.detach(|| encrypt_dir(&key, path.into_os_string(), &options))?;
+    to_python(py, &report)
+}
+
+/// Decrypts pack folder in place and returns report
+#[pyfunction]
+#[pyo3(signature = (path, key, *, jobs=0))]
+fn decrypt<'py>(
+    py: Python<'py>,
+    path: PathBuf,
+    key: String,
+    jobs: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    check_key(&key)?;
+    let options = decrypt_options(jobs);
+    let report = py.detach(|| decrypt_dir(&key, path.into_os_string(), &options))?;
+    to_python(py, &report)
+}
+
+/// Encrypts every pack of .mcpack/.mcaddon with its own random key. Returns
+/// path of encrypted archive and keys with reports of every pack
+#[pyfunction]
+#[pyo3(signature = (path, *, minify=false, lint=true, strict_json=false, force=false, jobs=0))]
+fn encrypt_archive<'py>(
+    py: Python<'py>,
+    path: PathBuf,
+    minify: bool,
+    lint: bool,
+    strict_json: bool,
+    force: bool,
+    jobs: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    let options = encrypt_options(minify, lint, strict_json, force, jobs);
+    let (archive, packs) = py.detach(|| encrypt_archive_file(&path, &options))?;
+    to_python(py, &json!({ "archive": archive, "packs": packs }))
+}
+
+/// Encrypts pack given as dict of relative paths to bytes, manifest.json
+/// included. Returns encrypted files together with contents.json
+#[pyfunction]
+#[pyo3(signature = (files, key, *, minify=false, lint=true, strict_json=false, force=false, jobs=0))]
+#[allow(clippy::too_many_arguments)]
+fn encrypt_files<'py>(
+    py: Python<'py>,
+    files: BTreeMap<String, Vec<u8>>,
+    key: String,
+    minify: bool,
+    lint: bool,
+    strict_json: bool,
+    force: bool,
+    jobs: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    check_key(&key)?;
+    let options = encrypt_options(minify, lint, strict_json, force, jobs);
+    // Manifest is taken as is, unlike encrypt_in_memory that writes typed one
+    let files = py.detach(|| {
+        let storage = MemoryStorage::from(normalize_paths(files)?);
+        encrypt_storage(&key, &storage, &options)?;
+        Ok::<_, PackEncryptionError>(storage.into_files())
+    })?;
+    to_bytes_dict(py, files)
+}
+
+/// Reverse of encrypt_files, contents.json is returned decrypted
+#[pyfunction]
+#[pyo3(signature = (files, key, *, jobs=0))]
+fn decrypt_files<'py>(
+    py: Python<'py>,
+    files: BTreeMap<String, Vec<u8>>,
+    key: String,
+    jobs: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    check_key(&key)?;
+    let options = decrypt_options(jobs);
+    let files = py.detach(|| {
+        let storage = MemoryStorage::from(normalize_paths(files)?);
+        decrypt_storage(&key, &storage, &options)?;
+        Ok::<_, PackEncryptionError>(storage.into_files())
+    })?;
+    to_bytes_dict(py, files)
+}
+
+/// Manifest and encryption state of pack folder, no key needed
+#[pyfunction]
+fn inspect<'py>(py: Python<'py>, path: PathBuf) -> PyResult<Bound<'py, PyAny>> {
+    let report = py.detach(|| inspect_dir(&path.into_os_string()))?;
+    to_python(py, &report)
+}
+
+/// Checks encrypted pack folder against its contents.json
+#[pyfunction]
+fn verify<'py>(py: Python<'py>, path: PathBuf, key: String) -> PyResult<Bound<'py, PyAny>> {
+    check_key(&key)?;
+    let report = py.detach(|| verify_dir(&key, &path.into_os_string()))?;
+    to_python(py, &report)
+}
+
+#[pymodule]
+pub fn kotik(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_function(wrap_pyfunction!(random_key, module)?)?;
+    module.add_function(wrap_pyfunction!(encrypt, module)?)?;
+    module.add_function(wrap_pyfunction!(decrypt, module)?)?;
+    module.add_function(wrap_pyfunction!(encrypt_archive, module)?)?;
+    module.add_function(wrap_pyfunction!(encrypt_files, module)?)?;
+    module.add_function(wrap_pyfunction!(decrypt_files, module)?)?;
+    module.add_function(wrap_pyfunction!(inspect, module)?)?;
+    module.add_function(wrap_pyfunction!(verify, module)?)?;
+
+    module.add("KotikError", py.get_type::<KotikError>())?;
+    module.add("InvalidKeyError", py.get_type::<InvalidKeyError>())?;
+    module.add("ManifestError", py.get_type::<ManifestError>())?;
+    module.add(
+        "AlreadyEncryptedError",
+        py.get_type::<AlreadyEncryptedError>(),
+    )?;
+    module.add("UnsafePathsError", py.get_type::<UnsafePathsError>())?;
+    module.add("ContentsError", py.get_type::<ContentsError>())?;
+    module.add("ArchiveError", py.get_type::<ArchiveError>())?;
+    module.add("LintError", py.get_type::<LintError>())?;
+    module.add("ProcessingError", py.get_type::<ProcessingError>())?;
+    Ok(())
+}
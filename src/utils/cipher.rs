@@ -1,19 +1,37 @@
 use cfb8::cipher::{AsyncStreamCipher, KeyIvInit};
 use rand::Rng;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum CipherError {
-    InvalidKeyLength,
+    // Length of the given key in bytes
+    InvalidKeyLength(usize),
     InvalidLength,
 }
 
+impl Display for CipherError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CipherError::InvalidKeyLength(length) => write!(
+                f,
+                "Key must be exactly 32 bytes long, but it's {} bytes",
+                length
+            ),
+            CipherError::InvalidLength => write!(f, "Invalid key or IV length for AES-256-CFB8"),
+        }
+    }
+}
+
+impl Error for CipherError {}
+
 type Aes256Cfb8Encryptor = cfb8::Encryptor<aes::Aes256>;
 type Aes256Cfb8Decryptor = cfb8::Decryptor<aes::Aes256>;
 
 fn get_key_as_bytes(key: &str) -> Result<&[u8], CipherError> {
     let key = key.as_bytes();
     if key.len() != 32 {
-        return Err(CipherError::InvalidKeyLength);
+        return Err(CipherError::InvalidKeyLength(key.len()));
     }
     Ok(key)
 }
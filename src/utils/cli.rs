@@ -1,10 +1,30 @@
-use crate::packs::encryption::decrypt::decrypt;
-use crate::packs::encryption::encrypt::encrypt;
-use crate::packs::pack_encryption::parse_pack_encryption_args;
+use crate::packs::archive::encrypt_archive;
+use crate::packs::build::build;
+use crate::packs::config::CONFIG_FILE;
+use crate::packs::encryption::decrypt::{decrypt, DecryptOptions};
+use crate::packs::encryption::encrypt::{encrypt, EncryptOptions, EncryptReport};
+use crate::packs::inspect::inspect;
+use crate::packs::lint::{lint_pack, LintSeverity};
+use crate::packs::pack_encryption::{
+    error_chain, CancellationToken, PackEncryptionError, ProcessingOptions,
+};
+use crate::packs::verify::verify;
+use crate::packs::watch::{watch, WatchOptions};
 use crate::utils::cipher::generate_random_key;
+use crate::utils::generate::{generate_completions, generate_man_page, SHELLS};
+use crate::utils::output::{print_line, print_result, CommandError, Context, OutputFormat, Report};
+use crate::utils::progress_bar::progress_bar;
+use crate::utils::shell::split_args;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::io;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+use std::time::Instant;
 
 #[derive(Debug)]
 struct Command<F> {
@@ -14,77 +34,570 @@ struct Command<F> {
     callback: F,
 }
 
-type Commands = [Command<fn(&[&str]) -> Result<(), String>>];
+type CommandResult = Result<Report, CommandError>;
+type Commands = [Command<fn(&[&str], &Context) -> CommandResult>];
 
 const COMMANDS: &Commands = &[
     Command {
         name: "help",
-        description: "Get info about all available commands. To get information about a specific command, specify the name as the second argument",
-        usage: "help <command(optional)>",
+        description: "Get info about all available commands. To get information about a specific command, specify the name as the second argument. Every command accepts --output json to print result as a single json document, --yes to skip confirmations, --jobs <n> to set number of worker threads and --fail-fast to stop on the first failed file",
+        usage: "help [<command>]",
         callback: help
     },
     Command {
         name: "encrypt",
-        description: "Encrypt a resource or behavior pack with given key and directory path. Use -r as key if you want to generate random key",
-        usage: "encrypt <key> <path>",
-        callback: |args| {
-            parse_pack_encryption_args(args, encrypt)
+        description: "Encrypt a resource or behavior pack with given key and directory path. Use -r as key if you want to generate random key. Pack is linted before encryption, use --no-lint to skip it. Use --minify to strip comments and whitespaces from json files before encryption, and --strict-json to fail on invalid json while minifying. Already encrypted packs are refused, use --force to encrypt them anyway",
+        usage: "encrypt [--no-lint] [--minify] [--strict-json] [--force] <key> <path>",
+        callback: |args, context| {
+            let (flags, args) = split_flags(args, ENCRYPT_FLAGS)?;
+            let options = encrypt_options(&flags, context);
+            let (key, path) = parse_pack_encryption_args(&args, context, "encrypt")?;
+
+            let report = encrypt(&key, path, &options).map_err(|e| error_with_key(e, &key))?;
+            Ok(Report::new(
+                format!("{}\nKey: {:?}", encrypt_report_text(&report), key),
+                json!({ "key": key, "report": report }),
+            ))
         }
     },
     Command {
         name: "decrypt",
-        description: "Decrypt a resource or behavior pack with given key and directory path. Use -r as key if you want to generate random key",
+        description: "Decrypt a resource or behavior pack with given key and directory path",
         usage: "decrypt <key> <path>",
-        callback: |args| {
-            parse_pack_encryption_args(args, decrypt)
+        callback: |args, context| {
+            let (key, path) = parse_pack_encryption_args(args, context, "decrypt")?;
+            let options = DecryptOptions {
+                processing: processing_options(context),
+                progress: progress_bar(context),
+            };
+
+            let report = decrypt(&key, path, &options)?;
+            Ok(Report::new(
+                format!(
+                    "Decrypted {} files ({} bytes) of pack {}",
+                    report.decrypted_files, report.decrypted_bytes, report.uuid
+                ),
+                json!({ "key": key, "report": report }),
+            ))
         }
     },
+    Command {
+        name: "encrypt_archive",
+        description: "Encrypt every pack inside .mcaddon or .mcpack archive with its own random key, including packs bundled into .mcaddon as .mcpack or .zip. Encrypted archive is written next to the original one. Supports the same flags as encrypt",
+        usage: "encrypt_archive [--no-lint] [--minify] [--strict-json] [--force] <path>",
+        callback: archive
+    },
+    Command {
+        name: "build",
+        description: "Encrypt a copy of the pack as described by a profile of kotik.toml project config and export it to the output folder together with the key file. Without --profile default_profile from config is used",
+        usage: "build [--profile <name>] [--config <path>]",
+        callback: |args, context| {
+            let (values, rest) = split_options(args, &["--profile", "--config"])?;
+            if let Some(arg) = rest.first() {
+                return Err(CommandError::from(format!("Unexpected argument '{}'", arg)));
+            }
+            let config_path = values
+                .get("--config")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(CONFIG_FILE));
+
+            let report = build(
+                &config_path,
+                values.get("--profile").copied(),
+                &encrypt_options(&[], context),
+            )?;
+
+            let mut text = format!(
+                "Built profile '{}'\n{}",
+                report.profile,
+                encrypt_report_text(&report.report)
+            );
+            if report.excluded > 0 {
+                text.push_str(&format!("\nExcluded files: {}", report.excluded));
+            }
+            for artifact in &report.artifacts {
+                text.push_str(&format!("\nArtifact: {}", artifact.display()));
+            }
+            text.push_str(&format!(
+                "\nKey: {:?} (saved to {})",
+                report.key,
+                report.key_file.display()
+            ));
+            Ok(Report::new(text, json!(report)))
+        }
+    },
+    Command {
+        name: "watch",
+        description: "Watch a pack folder and keep its encrypted copy in the output folder up to date. Changed files are re-encrypted with the same keys, contents.json is regenerated on every change. Other files of the output folder are left as is. Reusing keys is fine only for testing, make release builds with encrypt or build. Without --key random key is used. Stop with Ctrl-C",
+        usage: "watch [--key <key>] --out <path> <path>",
+        callback: watch_command
+    },
+    Command {
+        name: "inspect",
+        description: "Show pack name, UUID, file counts, sizes and subpacks. Works for both plain and encrypted packs, no key needed",
+        usage: "inspect <path>",
+        callback: |args, _| {
+            let path = parse_path_arg(args, "inspect")?;
+            let report = inspect(&path)?;
+
+            let mut text = format!(
+                "Pack: {}\nUUID: {}\nEncrypted: {}\nFiles: {} ({} bytes), folders: {}",
+                report.name.as_deref().unwrap_or("-"),
+                report.uuid.as_deref().unwrap_or("-"),
+                if report.encrypted { "yes" } else { "no" },
+                report.files,
+                report.bytes,
+                report.directories
+            );
+            if let Some(e) = &report.manifest_error {
+                text.push_str(&format!("\nManifest: {}", e));
+            }
+            for stats in &report.subpacks {
+                text.push_str(&format!(
+                    "\nSubpack \"{}\" ({}): {} files, {} bytes",
+                    stats.name, stats.folder_name, stats.files, stats.bytes
+                ));
+            }
+
+            Ok(Report::new(text, json!(report)))
+        }
+    },
+    Command {
+        name: "verify",
+        description: "Check that encrypted pack can be decrypted with given key, that every file listed in contents.json exists and stays inside the pack, and every file of the pack is listed. Files are not changed",
+        usage: "verify <key> <path>",
+        callback: |args, _| {
+            let Some((key, args)) = args.split_first() else {
+                return Err(CommandError::from(String::from(
+                    "Key and path are required. Use 'help verify' to get usage.",
+                )));
+            };
+            let path = parse_path_arg(args, "verify")?;
+            let report = verify(key, &path)?;
+
+            let mut text = format!("Pack {}: {} entries", report.uuid, report.entries);
+            if report.manifest_uuid.as_ref() != Some(&report.uuid) {
+                text.push_str("\nUUID in contents.json doesn't match manifest.json");
+            }
+            for path in &report.missing {
+                text.push_str(&format!("\nMissing: {}", path));
+            }
+            for path in &report.unlisted {
+                text.push_str(&format!("\nNot listed in contents.json: {}", path));
+            }
+            for path in &report.unsafe_paths {
+                text.push_str(&format!("\nOutside of the pack: {}", path));
+            }
+
+            if !report.is_valid() {
+                return Err(CommandError::with_details(
+                    format!("Verification failed\n{}", text),
+                    json!({ "report": report }),
+                ));
+            }
+            Ok(Report::new(format!("{}\nPack is valid", text), json!({ "report": report })))
+        }
+    },
+    Command {
+        name: "lint",
+        description: "Check pack for broken json, missing textures, case mismatches in paths, oversized textures and files that Bedrock ignores",
+        usage: "lint <path>",
+        callback: lint
+    },
     Command {
         name: "random_key",
         description: "Generate random 256 bit valid key for encryption",
         usage: "random_key",
-        callback: |_| {
-            println!("{}", generate_random_key());
-            Ok(())
+        callback: |_, _| {
+            let key = generate_random_key();
+            Ok(Report::new(key.clone(), json!({ "key": key })))
+        }
+    },
+    Command {
+        name: "completions",
+        description: "Print completion script for bash, zsh, fish or powershell. For example, add 'source <(kotik completions bash)' to ~/.bashrc",
+        usage: "completions <shell>",
+        callback: |args, _| {
+            let shell = match args {
+                [shell] => shell,
+                _ => {
+                    return Err(CommandError::from(format!(
+                        "Specify one shell of: {}",
+                        SHELLS.join(", ")
+                    )))
+                }
+            };
+            let script = generate_completions(shell)?;
+            Ok(Report::new(script.trim_end(), json!({ "shell": shell, "script": script })))
+        }
+    },
+    Command {
+        name: "man",
+        description: "Print man page in roff format. For example, 'kotik man > kotik.1'",
+        usage: "man",
+        callback: |_, _| {
+            let page = generate_man_page()?;
+            Ok(Report::new(page.trim_end(), json!({ "man": page })))
         }
     },
     Command {
         name: "exit",
         description: "Exit the program",
         usage: "exit",
-        callback: |_| {
+        callback: |_, _| {
             exit(0);
         }
     },
-    #[cfg(debug_assertions)]
-    Command {
-        name: "test",
-        description: "Test function",
-        usage: "test",
-        callback: test
-    }
 ];
 
-fn test(_: &[&str]) -> Result<(), String> {
-    // let key = "s5s5ejuDru4uchuF2drUFuthaspAbepE";
-    // let encrypted_content = read(r"...").unwrap();
-    // let decrypted_content = aes256_cfb8_decrypt(key, encrypted_content[0x100..].to_vec()).unwrap();
-    // let content = String::from_utf8(decrypted_content).unwrap();
-    // println!("{}", content);
-    Ok(())
+const ENCRYPT_FLAGS: &[&str] = &["--no-lint", "--minify", "--strict-json", "--force"];
+
+fn encrypt_options(flags: &[&str], context: &Context) -> EncryptOptions {
+    EncryptOptions {
+        lint: !flags.contains(&"--no-lint"),
+        minify: flags.contains(&"--minify"),
+        strict_json: flags.contains(&"--strict-json"),
+        force: flags.contains(&"--force"),
+        processing: processing_options(context),
+        progress: progress_bar(context),
+    }
+}
+
+// Key is shown even on failure, otherwise partially encrypted pack
+// with random key could never be decrypted
+fn error_with_key(e: PackEncryptionError, key: &str) -> CommandError {
+    let mut e = CommandError::from(e);
+    e.message.push_str(&format!("\nKey: {:?}", key));
+    e.details.insert(String::from("key"), json!(key));
+    e
+}
+
+fn processing_options(context: &Context) -> ProcessingOptions {
+    ProcessingOptions {
+        jobs: context.jobs,
+        fail_fast: context.fail_fast,
+        cancellation: CANCELLATION.clone(),
+    }
+}
+
+// Separates --flags from the rest of arguments
+fn split_flags<'a>(
+    args: &[&'a str],
+    allowed: &[&str],
+) -> Result<(Vec<&'a str>, Vec<&'a str>), String> {
+    let (flags, rest): (Vec<&str>, Vec<&str>) = args.iter().partition(|arg| arg.starts_with("--"));
+
+    if let Some(flag) = flags.iter().find(|flag| !allowed.contains(flag)) {
+        return Err(format!("Unknown flag '{}'", flag));
+    }
+    Ok((flags, rest))
+}
+
+fn watch_command(args: &[&str], context: &Context) -> CommandResult {
+    let (values, rest) = split_options(args, &["--key", "--out"])?;
+    let source = parse_path_arg(&rest, "watch")?;
+    let output = values
+        .get("--out")
+        .ok_or(String::from("No output folder provided. Use --out <path>"))?;
+    let key = values
+        .get("--key")
+        .map(|key| key.to_string())
+        .unwrap_or_else(generate_random_key);
+
+    if !confirm(
+        context,
+        format!(
+            "Output folder {:?} will be replaced with encrypted copy of {:?}. Continue?",
+            output, source
+        ),
+    )? {
+        return Err(CommandError::from(PackEncryptionError::Abort));
+    }
+
+    let options = WatchOptions {
+        processing: processing_options(context),
+        progress: progress_bar(context),
+    };
+    let output_format = context.output;
+    match output_format {
+        OutputFormat::Text => print_line(format_args!(
+            "Key: {:?}\nWatching {:?}, press Ctrl-C to stop",
+            key, source
+        )),
+        OutputFormat::Json => print_line(json!({ "event": "started", "key": key })),
+    }
+
+    let mut updates = 0;
+    watch(
+        Path::new(&source),
+        Path::new(output),
+        &key,
+        &options,
+        |result| {
+            updates += 1;
+            match (output_format, result) {
+                (OutputFormat::Text, Ok(update)) => print_line(format_args!(
+                    "Updated {} files, removed {}, contents.json lists {} entries",
+                    update.updated.len(),
+                    update.removed.len(),
+                    update.entries
+                )),
+                (OutputFormat::Text, Err(e)) => print_line(CommandError::from(e).message),
+                (OutputFormat::Json, Ok(update)) => {
+                    print_line(json!({ "event": "updated", "update": update }))
+                }
+                (OutputFormat::Json, Err(e)) => {
+                    let e = CommandError::from(e);
+                    let mut event = e.details;
+                    event.insert(String::from("event"), json!("failed"));
+                    event.insert(String::from("error"), json!(e.message));
+                    print_line(Value::Object(event))
+                }
+            }
+        },
+    )?;
+
+    Ok(Report::new(
+        format!("Stopped watching after {} updates", updates),
+        json!({ "key": key, "updates": updates }),
+    ))
+}
+
+// Separates --name <value> and --name=<value> options from the rest of arguments
+fn split_options<'a>(
+    args: &[&'a str],
+    allowed: &[&'static str],
+) -> Result<(HashMap<&'static str, &'a str>, Vec<&'a str>), String> {
+    let mut values = HashMap::new();
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            rest.push(*arg);
+            continue;
+        }
+
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (*arg, args.next().copied()),
+        };
+        let name = allowed
+            .iter()
+            .find(|allowed| **allowed == name)
+            .ok_or(format!("Unknown option '{}'", name))?;
+        let value = value.ok_or(format!("Option '{}' needs a value", name))?;
+        values.insert(*name, value);
+    }
+
+    Ok((values, rest))
+}
+
+// Confirmation can't be asked when output is json, since prompt would break it
+fn confirm(context: &Context, prompt: String) -> Result<bool, String> {
+    if context.assume_yes {
+        return Ok(true);
+    }
+    if context.output == OutputFormat::Json {
+        return Err(String::from(
+            "This command needs confirmation. Use --yes together with --output json",
+        ));
+    }
+    let choice = get_choice(prompt);
+    // Ctrl-C while waiting for the answer means no
+    Ok(choice && !CANCELLATION.is_cancelled())
+}
+
+// Returns key and path for encrypt/decrypt
+fn parse_pack_encryption_args(
+    args: &[&str],
+    context: &Context,
+    action: &str,
+) -> Result<(String, OsString), CommandError> {
+    if args.is_empty() {
+        return Err(CommandError::from(String::from(
+            "No arguments provided. Use 'help' to get list of all available commands.",
+        )));
+    }
+
+    if args.len() > 2 {
+        return Err(CommandError::from(format!(
+            "Too many arguments. Use quotes for paths with spaces, for example: {} <key> \"My Pack\"",
+            action
+        )));
+    }
+
+    // For cases then user somehow forgot to specify path
+    let path_arg = match args.get(1) {
+        Some(path) => path.to_string(),
+        None => {
+            if context.output == OutputFormat::Json {
+                return Err(CommandError::from(String::from("No path provided")));
+            }
+
+            let mut input = String::new();
+            get_input(
+                "It looks like you forgot that you need to specify the folder path. Enter path:",
+                &mut input,
+            );
+            input.trim().to_owned()
+        }
+    };
+
+    let key = if args[0] == "-r" {
+        generate_random_key()
+    } else {
+        args[0].to_owned()
+    };
+    let path = OsString::from(path_arg);
+
+    // Ask user once again
+    if !confirm(
+        context,
+        format!(
+            "Are you sure you want to {} the data on the following path: {:#?}? Files will be rewrote permanently.",
+            action, path
+        ),
+    )? {
+        return Err(CommandError::from(PackEncryptionError::Abort));
+    }
+
+    Ok((key, path))
+}
+
+fn parse_path_arg(args: &[&str], command: &str) -> Result<OsString, String> {
+    if args.is_empty() {
+        return Err(format!(
+            "No path provided. Use 'help {}' to get usage.",
+            command
+        ));
+    }
+
+    if args.len() > 1 {
+        return Err(format!(
+            "Too many arguments. Use quotes for paths with spaces, for example: {} \"My Pack\"",
+            command
+        ));
+    }
+
+    Ok(OsString::from(args[0]))
+}
+
+fn encrypt_report_text(report: &EncryptReport) -> String {
+    let mut lines = Vec::new();
+
+    for warning in &report.lint_warnings {
+        lines.push(format!("Warning: {}", warning));
+    }
+    if let Some(minify) = &report.minify {
+        for e in &minify.skipped {
+            lines.push(format!("Skipped minifying: {}", error_chain(e)));
+        }
+        lines.push(format!(
+            "Minified {} files, saved {} bytes",
+            minify.files, minify.saved_bytes
+        ));
+    }
+    for stats in &report.subpacks {
+        lines.push(format!(
+            "Subpack \"{}\" ({}, memory tier {}): {} files, {} encrypted, {} bytes",
+            stats.name,
+            stats.folder_name,
+            stats
+                .memory_tier
+                .map_or(String::from("-"), |tier| tier.to_string()),
+            stats.files,
+            stats.encrypted_files,
+            stats.bytes
+        ));
+    }
+    lines.push(format!(
+        "Encrypted {} of {} files ({} bytes) of pack {}",
+        report.encrypted_files, report.files, report.encrypted_bytes, report.uuid
+    ));
+
+    lines.join("\n")
+}
+
+fn archive(args: &[&str], context: &Context) -> CommandResult {
+    let (flags, args) = split_flags(args, ENCRYPT_FLAGS)?;
+    let path = parse_path_arg(&args, "encrypt_archive")?;
+
+    let (output_path, packs) =
+        encrypt_archive(Path::new(&path), &encrypt_options(&flags, context))?;
+
+    let mut lines = Vec::new();
+    for pack in &packs {
+        let location = if pack.path.is_empty() {
+            "/"
+        } else {
+            &pack.path
+        };
+        lines.push(format!(
+            "Pack {} ({}) - Key: {:?}",
+            pack.uuid, location, pack.key
+        ));
+    }
+    lines.push(format!("Encrypted archive: {}", output_path.display()));
+
+    Ok(Report::new(
+        lines.join("\n"),
+        json!({ "output_path": output_path, "packs": packs }),
+    ))
+}
+
+fn lint(args: &[&str], _: &Context) -> CommandResult {
+    let path = parse_path_arg(args, "lint")?;
+    let issues = lint_pack(&path).map_err(|e| format!("Can't lint pack: {}", e))?;
+
+    let text = issues
+        .iter()
+        .map(|issue| {
+            let severity = match issue.severity {
+                LintSeverity::Warning => "warning",
+                LintSeverity::Error => "error",
+            };
+            format!("[{}] {}", severity, issue)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == LintSeverity::Error)
+        .count();
+    if errors > 0 {
+        return Err(CommandError::with_details(
+            format!(
+                "{}\nFound {} errors and {} warnings",
+                text,
+                errors,
+                issues.len() - errors
+            ),
+            json!({ "lint": issues }),
+        ));
+    }
+
+    Ok(Report::new(
+        format!("{}\nNo errors found, {} warnings", text, issues.len())
+            .trim_start()
+            .to_owned(),
+        json!({ "lint": issues }),
+    ))
 }
 
-fn help(context: &[&str]) -> Result<(), String> {
+fn help(context: &[&str], _: &Context) -> CommandResult {
     if context.is_empty() {
-        println!(
-            "{}",
-            COMMANDS
-                .iter()
-                .map(|cmd| format!("\"{}\" - {}", cmd.name, cmd.description))
-                .collect::<Vec<_>>()
-                .join("\n")
-        );
-        return Ok(());
+        let text = COMMANDS
+            .iter()
+            .map(|cmd| format!("\"{}\" - {}", cmd.name, cmd.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let commands = COMMANDS
+            .iter()
+            .map(|cmd| json!({ "name": cmd.name, "description": cmd.description, "usage": cmd.usage }))
+            .collect::<Vec<_>>();
+        return Ok(Report::new(text, json!({ "commands": commands })));
     }
 
     let cmd = COMMANDS
@@ -92,23 +605,115 @@ fn help(context: &[&str]) -> Result<(), String> {
         .find(|cmd| cmd.name == context[0])
         .ok_or(format!("Command '{}' not found", context[0]))?;
 
-    println!(
-        "\"{}\" - {}\nUsage: \"{}\"\n",
-        cmd.name, cmd.description, cmd.usage
-    );
-    Ok(())
+    Ok(Report::new(
+        format!(
+            "\"{}\" - {}\nUsage: \"{}\"\n",
+            cmd.name, cmd.description, cmd.usage
+        ),
+        json!({ "name": cmd.name, "description": cmd.description, "usage": cmd.usage }),
+    ))
 }
 
-pub fn handle_user_input(input: &str) -> Result<(), String> {
-    let args: Vec<&str> = input.split_whitespace().collect();
-    if let Some(cmd) = COMMANDS.iter().find(|cmd| cmd.name == args[0]) {
-        (cmd.callback)(&args[1..])?;
-        return Ok(());
+// Ctrl-C cancels running command, and exits when nothing is running
+static CANCELLATION: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::default);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+pub fn handle_interrupts() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if RUNNING.load(Ordering::SeqCst) {
+            CANCELLATION.cancel();
+        } else {
+            exit(130);
+        }
+    })
+}
+
+// Runs single command with its arguments and prints the result.
+// Returns false if command failed
+pub fn run_command(args: &[&str]) -> bool {
+    let started = Instant::now();
+    let (context, args) = match Context::from_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            print_result(
+                &Context::default(),
+                "",
+                Err(CommandError::from(e)),
+                started.elapsed(),
+            );
+            return false;
+        }
+    };
+
+    // Nothing to do with empty input
+    let Some((name, args)) = args.split_first() else {
+        return true;
+    };
+
+    CANCELLATION.reset();
+    RUNNING.store(true, Ordering::SeqCst);
+    let result = match COMMANDS.iter().find(|cmd| cmd.name == *name) {
+        Some(cmd) => (cmd.callback)(args, &context),
+        None => Err(CommandError::from(format!(
+            "Unknown command '{}'. Use 'help' to get full list of commands",
+            name
+        ))),
+    };
+    RUNNING.store(false, Ordering::SeqCst);
+
+    let ok = result.is_ok();
+    print_result(&context, name, result, started.elapsed());
+    ok
+}
+
+pub fn handle_user_input(input: &str) -> bool {
+    let args = match split_args(input) {
+        Ok(args) => args,
+        Err(e) => {
+            print_result(
+                &Context::default(),
+                "",
+                Err(CommandError::from(e.to_string())),
+                Default::default(),
+            );
+            return false;
+        }
+    };
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    run_command(&args)
+}
+
+// Keys are secrets, so lines with them aren't saved to the history file.
+// Lines that can't be parsed are treated as having a key too
+pub fn has_key_argument(input: &str) -> bool {
+    let Ok(args) = split_args(input) else {
+        return true;
+    };
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let Ok((_, args)) = Context::from_args(&args) else {
+        return true;
+    };
+
+    match args.split_first() {
+        // Random key is printed, not typed
+        Some((&"encrypt", args)) => args.iter().find(|arg| !arg.starts_with("--")) != Some(&"-r"),
+        Some((&("decrypt" | "verify"), _)) => true,
+        Some((&"watch", args)) => args
+            .iter()
+            .any(|arg| *arg == "--key" || arg.starts_with("--key=")),
+        _ => false,
     }
-    Err(format!(
-        "Unknown command '{}'. Use 'help' to get full list of commands",
-        args[0]
-    ))
+}
+
+pub fn command_names() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().map(|cmd| cmd.name)
+}
+
+// Name, description and usage of every command
+pub fn command_metadata() -> impl Iterator<Item = (&'static str, &'static str, &'static str)> {
+    COMMANDS
+        .iter()
+        .map(|cmd| (cmd.name, cmd.description, cmd.usage))
 }
 
 pub fn get_input(prompt: &str, buffer: &mut String) {
@@ -133,6 +738,8 @@ pub fn get_choice(prompt: String) -> bool {
 
         input.clear();
         match io::stdin().read_line(&mut input) {
+            // Nobody will answer when input is closed
+            Ok(0) => return false,
             Ok(_) => match input.trim().to_lowercase().as_str() {
                 "y" | "yes" => return true,
                 "n" | "no" => return false,
@@ -146,3 +753,35 @@ pub fn get_choice(prompt: String) -> bool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packs::fixtures::KEY;
+
+    #[test]
+    fn lines_with_keys() {
+        for line in [
+            format!("encrypt {} pack", KEY),
+            format!("encrypt --minify {} \"My Pack\"", KEY),
+            format!("--output json decrypt {} pack", KEY),
+            format!("verify {} pack", KEY),
+            format!("watch --key {} --out out pack", KEY),
+            format!("watch --out out --key={} pack", KEY),
+            format!("decrypt \"{} pack", KEY),
+        ] {
+            assert!(has_key_argument(&line), "{}", line);
+        }
+
+        for line in [
+            "encrypt -r pack",
+            "encrypt --force -r \"My Pack\"",
+            "watch --out out pack",
+            "inspect pack",
+            "help encrypt",
+            "",
+        ] {
+            assert!(!has_key_argument(line), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::utils::cli::command_metadata;
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, ValueHint};
+use clap_complete::Shell;
+
+// Shell completions and man page are generated from the same metadata
+// as help command, so usage strings follow a tiny grammar:
+// [--flag] is an optional flag, [--option <value>] and --option <value> are
+// optional and required options with value, <arg> is a required and [<arg>] is an optional argument
+
+pub const SHELLS: [&str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+const BIN_NAME: &str = "kotik";
+
+// Completion of the value depends on its name
+fn with_value_hint(arg: Arg, name: &'static str) -> Arg {
+    let arg = arg.value_name(name);
+    match name {
+        "path" => arg.value_hint(ValueHint::AnyPath),
+        "command" => arg.value_parser(PossibleValuesParser::new(
+            command_metadata().map(|(name, _, _)| name),
+        )),
+        "shell" => arg.value_parser(PossibleValuesParser::new(SHELLS)),
+        _ => arg,
+    }
+}
+
+fn subcommand(name: &'static str, description: &'static str, usage: &'static str) -> clap::Command {
+    // First sentence is short enough for completion menus
+    let about = description.split(". ").next().unwrap_or(description);
+    let mut command = clap::Command::new(name)
+        .about(about)
+        .long_about(description);
+
+    let mut index = 0;
+    let mut tokens = usage.split_whitespace().skip(1).peekable();
+    while let Some(token) = tokens.next() {
+        let optional = token.starts_with('[');
+        // [--option <value>] is split into two tokens
+        let has_value = if optional {
+            !token.ends_with(']')
+        } else {
+            tokens.peek().is_some_and(|next| next.starts_with('<'))
+        };
+        let token = token.trim_start_matches('[').trim_end_matches(']');
+
+        if let Some(flag) = token.strip_prefix("--") {
+            let arg = Arg::new(flag).long(flag).required(!optional);
+            let value = if has_value { tokens.next() } else { None }
+                .map(|value| value.trim_end_matches(']'))
+                .and_then(|value| value.strip_prefix('<'))
+                .and_then(|value| value.strip_suffix('>'));
+            command = command.arg(match value {
+                Some(value) => with_value_hint(arg.action(ArgAction::Set), value),
+                None => arg.action(ArgAction::SetTrue),
+            });
+        } else if let Some(name) = token.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+            index += 1;
+            let arg = Arg::new(name).index(index).required(!optional);
+            command = command.arg(with_value_hint(arg, name));
+        }
+    }
+
+    command
+}
+
+pub fn clap_command() -> clap::Command {
+    let global_flag = |name: &'static str, help: &'static str| {
+        Arg::new(name)
+            .long(name)
+            .global(true)
+            .action(ArgAction::SetTrue)
+            .help(help)
+    };
+
+    clap::Command::new(BIN_NAME)
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .version(env!("CARGO_PKG_VERSION"))
+        // Our own help command is listed instead
+        .disable_help_subcommand(true)
+        .long_about(
+            "Pack encryption utility for Minecraft Bedrock. \
+             Without arguments it starts in interactive mode",
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .global(true)
+                .value_name("FORMAT")
+                .value_parser(PossibleValuesParser::new(["text", "json"]))
+                .help("Print result as text or as a single json document"),
+        )
+        .arg(global_flag("yes", "Skip confirmation prompts"))
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .global(true)
+                .value_name("N")
+                .help("Number of worker threads, one per CPU core by default"),
+        )
+        .arg(global_flag(
+            "fail-fast",
+            "Stop processing after the first failed file",
+        ))
+        .subcommands(
+            command_metadata()
+                .map(|(name, description, usage)| subcommand(name, description, usage)),
+        )
+}
+
+pub fn generate_completions(shell: &str) -> Result<String, String> {
+    let shell = match shell {
+        "bash" => Shell::Bash,
+        "zsh" => Shell::Zsh,
+        "fish" => Shell::Fish,
+        "powershell" => Shell::PowerShell,
+        shell => {
+            return Err(format!(
+                "Unknown shell '{}'. Use one of: {}",
+                shell,
+                SHELLS.join(", ")
+            ))
+        }
+    };
+
+    let mut buffer = Vec::new();
+    clap_complete::generate(shell, &mut clap_command(), BIN_NAME, &mut buffer);
+    String::from_utf8(buffer).map_err(|e| e.to_string())
+}
+
+pub fn generate_man_page() -> Result<String, String> {
+    let mut buffer = Vec::new();
+    clap_mangen::Man::new(clap_command())
+        .render(&mut buffer)
+        .map_err(|e| e.to_string())?;
+    String::from_utf8(buffer).map_err(|e| e.to_string())
+}
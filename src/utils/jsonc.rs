@@ -0,0 +1,474 @@
+use serde_json::{Map, Number, Value};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+// Minecraft reads json with comments (both // and /* */) and trailing
+// commas, so we need our own parser. Output is regular serde_json Value
+
+// Deeply nested json can overflow the stack, real packs never get close
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsoncError {
+    // Both are 1-based, column is counted in chars
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for JsoncError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl Error for JsoncError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
+    Comma,
+    String(String),
+    Number(Number),
+    True,
+    False,
+    Null,
+    Eof,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        // Files saved by some Windows editors start with BOM
+        let source = source.strip_prefix('\u{FEFF}').unwrap_or(source);
+        Self {
+            source,
+            chars: source.char_indices().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> JsoncError {
+        JsoncError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn offset(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map_or(self.source.len(), |(offset, _)| *offset)
+    }
+
+    // Skips whitespaces and both comment styles
+    fn skip_trivia(&mut self) -> Result<(), JsoncError> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('/') => {
+                    let (line, column) = (self.line, self.column);
+                    self.bump();
+                    match self.bump() {
+                        Some('/') => {
+                            while self.peek().is_some_and(|c| c != '\n') {
+                                self.bump();
+                            }
+                        }
+                        Some('*') => loop {
+                            match self.bump() {
+                                Some('*') if self.peek() == Some('/') => {
+                                    self.bump();
+                                    break;
+                                }
+                                Some(_) => {}
+                                None => {
+                                    return Err(JsoncError {
+                                        line,
+                                        column,
+                                        message: String::from("Unterminated block comment"),
+                                    })
+                                }
+                            }
+                        },
+                        _ => {
+                            return Err(JsoncError {
+                                line,
+                                column,
+                                message: String::from("Unexpected character '/'"),
+                            })
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, JsoncError> {
+        self.skip_trivia()?;
+        let (line, column) = (self.line, self.column);
+
+        let kind = match self.peek() {
+            None => TokenKind::Eof,
+            Some(c @ ('{' | '}' | '[' | ']' | ':' | ',')) => {
+                self.bump();
+                match c {
+                    '{' => TokenKind::LeftBrace,
+                    '}' => TokenKind::RightBrace,
+                    '[' => TokenKind::LeftBracket,
+                    ']' => TokenKind::RightBracket,
+                    ':' => TokenKind::Colon,
+                    _ => TokenKind::Comma,
+                }
+            }
+            Some('"') => TokenKind::String(self.string()?),
+            Some('-' | '0'..='9') => TokenKind::Number(self.number()?),
+            Some(c) if c.is_ascii_alphabetic() => self.keyword()?,
+            Some(c) => return Err(self.error(format!("Unexpected character '{}'", c))),
+        };
+
+        Ok(Token { kind, line, column })
+    }
+
+    fn string(&mut self) -> Result<String, JsoncError> {
+        let (line, column) = (self.line, self.column);
+        // Opening quote
+        self.bump();
+
+        let mut result = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(result),
+                Some('\\') => match self.bump() {
+                    Some('"') => result.push('"'),
+                    Some('\\') => result.push('\\'),
+                    Some('/') => result.push('/'),
+                    Some('b') => result.push('\u{0008}'),
+                    Some('f') => result.push('\u{000C}'),
+                    Some('n') => result.push('\n'),
+                    Some('r') => result.push('\r'),
+                    Some('t') => result.push('\t'),
+                    Some('u') => result.push(self.unicode_escape()?),
+                    Some(c) => return Err(self.error(format!("Invalid escape '\\{}'", c))),
+                    None => break,
+                },
+                // Only U+0000 to U+001F are forbidden in json strings
+                Some(c) if c < '\u{20}' && c != '\t' => {
+                    return Err(self.error("Control character in string"))
+                }
+                Some(c) => result.push(c),
+                None => break,
+            }
+        }
+
+        Err(JsoncError {
+            line,
+            column,
+            message: String::from("Unterminated string"),
+        })
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsoncError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .bump()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("Invalid unicode escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    // \uXXXX, including surrogate pairs for chars outside of BMP
+    fn unicode_escape(&mut self) -> Result<char, JsoncError> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("Invalid unicode escape"));
+        }
+
+        if self.bump() != Some('\\') || self.bump() != Some('u') {
+            return Err(self.error("Unpaired surrogate in unicode escape"));
+        }
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("Unpaired surrogate in unicode escape"));
+        }
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn digits(&mut self) -> usize {
+        let mut count = 0;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            count += 1;
+        }
+        count
+    }
+
+    // Strict json number grammar, then serde_json does the conversion
+    fn number(&mut self) -> Result<Number, JsoncError> {
+        let (line, column) = (self.line, self.column);
+        let start = self.offset();
+
+        if self.peek() == Some('-') {
+            self.bump();
+        }
+
+        let invalid = |message: &str| JsoncError {
+            line,
+            column,
+            message: message.to_owned(),
+        };
+
+        match self.peek() {
+            Some('0') => {
+                self.bump();
+                if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    return Err(invalid("Leading zeros are not allowed in numbers"));
+                }
+            }
+            Some('1'..='9') => {
+                self.digits();
+            }
+            _ => return Err(invalid("Invalid number")),
+        }
+
+        if self.peek() == Some('.') {
+            self.bump();
+            if self.digits() == 0 {
+                return Err(invalid("Invalid number"));
+            }
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.bump();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.bump();
+            }
+            if self.digits() == 0 {
+                return Err(invalid("Invalid number"));
+            }
+        }
+
+        let end = self.offset();
+        self.source[start..end]
+            .parse::<Number>()
+            .map_err(|_| invalid("Number is out of range"))
+    }
+
+    fn keyword(&mut self) -> Result<TokenKind, JsoncError> {
+        let (line, column) = (self.line, self.column);
+        let start = self.offset();
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.bump();
+        }
+
+        match &self.source[start..self.offset()] {
+            "true" => Ok(TokenKind::True),
+            "false" => Ok(TokenKind::False),
+            "null" => Ok(TokenKind::Null),
+            word => Err(JsoncError {
+                line,
+                column,
+                message: format!("Unexpected identifier '{}'", word),
+            }),
+        }
+    }
+}
+
+struct Parser<'a> {
+    lexer: Lexer<'a>,
+    current: Token,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Result<Self, JsoncError> {
+        let mut lexer = Lexer::new(source);
+        let current = lexer.next_token()?;
+        Ok(Self {
+            lexer,
+            current,
+            depth: 0,
+        })
+    }
+
+    fn advance(&mut self) -> Result<Token, JsoncError> {
+        let next = self.lexer.next_token()?;
+        Ok(std::mem::replace(&mut self.current, next))
+    }
+
+    fn error(&self, message: impl Into<String>) -> JsoncError {
+        JsoncError {
+            line: self.current.line,
+            column: self.current.column,
+            message: message.into(),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> JsoncError {
+        let found = match &self.current.kind {
+            TokenKind::Eof => String::from("end of file"),
+            TokenKind::String(_) => String::from("string"),
+            TokenKind::Number(number) => format!("number {}", number),
+            TokenKind::LeftBrace => String::from("'{'"),
+            TokenKind::RightBrace => String::from("'}'"),
+            TokenKind::LeftBracket => String::from("'['"),
+            TokenKind::RightBracket => String::from("']'"),
+            TokenKind::Colon => String::from("':'"),
+            TokenKind::Comma => String::from("','"),
+            TokenKind::True => String::from("true"),
+            TokenKind::False => String::from("false"),
+            TokenKind::Null => String::from("null"),
+        };
+        self.error(format!("Expected {}, found {}", expected, found))
+    }
+
+    fn value(&mut self) -> Result<Value, JsoncError> {
+        match self.current.kind {
+            TokenKind::LeftBrace => self.nested(Self::object),
+            TokenKind::LeftBracket => self.nested(Self::array),
+            TokenKind::RightBrace
+            | TokenKind::RightBracket
+            | TokenKind::Colon
+            | TokenKind::Comma
+            | TokenKind::Eof => Err(self.unexpected("value")),
+            _ => Ok(match self.advance()?.kind {
+                TokenKind::String(string) => Value::String(string),
+                TokenKind::Number(number) => Value::Number(number),
+                TokenKind::True => Value::Bool(true),
+                TokenKind::False => Value::Bool(false),
+                _ => Value::Null,
+            }),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, JsoncError>,
+    ) -> Result<Value, JsoncError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("Maximum nesting depth exceeded"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Value, JsoncError> {
+        // Opening brace
+        self.advance()?;
+        let mut map = Map::new();
+
+        loop {
+            // Empty object or trailing comma
+            if self.current.kind == TokenKind::RightBrace {
+                self.advance()?;
+                return Ok(Value::Object(map));
+            }
+
+            let TokenKind::String(key) = &self.current.kind else {
+                return Err(self.unexpected("string key or '}'"));
+            };
+            let key = key.clone();
+            self.advance()?;
+
+            if self.current.kind != TokenKind::Colon {
+                return Err(self.unexpected("':'"));
+            }
+            self.advance()?;
+
+            let value = self.value()?;
+            map.insert(key, value);
+
+            match self.current.kind {
+                TokenKind::Comma => {
+                    self.advance()?;
+                }
+                TokenKind::RightBrace => {}
+                _ => return Err(self.unexpected("',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, JsoncError> {
+        // Opening bracket
+        self.advance()?;
+        let mut items = Vec::new();
+
+        loop {
+            // Empty array or trailing comma
+            if self.current.kind == TokenKind::RightBracket {
+                self.advance()?;
+                return Ok(Value::Array(items));
+            }
+
+            items.push(self.value()?);
+
+            match self.current.kind {
+                TokenKind::Comma => {
+                    self.advance()?;
+                }
+                TokenKind::RightBracket => {}
+                _ => return Err(self.unexpected("',' or ']'")),
+            }
+        }
+    }
+}
+
+pub fn parse_jsonc(content: &str) -> Result<Value, JsoncError> {
+    let mut parser = Parser::new(content)?;
+    let value = parser.value()?;
+
+    if parser.current.kind != TokenKind::Eof {
+        return Err(parser.unexpected("end of file"));
+    }
+    Ok(value)
+}
@@ -1,3 +1,11 @@
 pub mod cipher;
+#[cfg(feature = "cli")]
 pub mod cli;
-pub mod comments;
+#[cfg(feature = "cli")]
+pub mod generate;
+pub mod jsonc;
+pub mod output;
+pub mod progress_bar;
+#[cfg(feature = "cli")]
+pub mod repl;
+pub mod shell;
@@ -0,0 +1,223 @@
+#[cfg(feature = "cli")]
+use crate::packs::build::BuildError;
+use crate::packs::pack_encryption::{error_chain, PackEncryptionError};
+#[cfg(feature = "cli")]
+use crate::packs::watch::WatchError;
+use serde_json::{json, Map, Value};
+use std::fmt::Display;
+use std::io::{stdout, ErrorKind, Write};
+use std::process::exit;
+use std::time::Duration;
+
+// Every command can print its result either as text for humans or
+// as a single json document for scripts and build dashboards
+
+const RED: &str = "\x1b[0;31m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Global options, that work with every command
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Context {
+    pub output: OutputFormat,
+    // Skip confirmation prompts
+    pub assume_yes: bool,
+    // Worker threads for file processing, 0 means one per core
+    pub jobs: usize,
+    // Stop processing after the first failed file
+    pub fail_fast: bool,
+}
+
+impl Context {
+    // Takes --output <text|json>, --jobs <n>, --fail-fast and --yes out of arguments
+    pub fn from_args<'a>(args: &[&'a str]) -> Result<(Self, Vec<&'a str>), String> {
+        let mut context = Context::default();
+        let mut rest = Vec::with_capacity(args.len());
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (name, value) = match *arg {
+                "--yes" => {
+                    context.assume_yes = true;
+                    continue;
+                }
+                "--fail-fast" => {
+                    context.fail_fast = true;
+                    continue;
+                }
+                name @ ("--output" | "--jobs") => (name, args.next().copied().unwrap_or_default()),
+                arg => match arg.split_once('=') {
+                    Some((name @ ("--output" | "--jobs"), value)) => (name, value),
+                    _ => {
+                        rest.push(arg);
+                        continue;
+                    }
+                },
+            };
+
+            if name == "--jobs" {
+                context.jobs = match value.parse() {
+                    Ok(jobs) if jobs > 0 => jobs,
+                    _ => {
+                        return Err(format!(
+                            "Invalid number of jobs '{}'. Use a positive number",
+                            value
+                        ))
+                    }
+                };
+                continue;
+            }
+
+            context.output = match value {
+                "text" => OutputFormat::Text,
+                "json" => OutputFormat::Json,
+                format => {
+                    return Err(format!(
+                        "Unknown output format '{}'. Use 'text' or 'json'",
+                        format
+                    ))
+                }
+            };
+        }
+
+        Ok((context, rest))
+    }
+}
+
+// Result of a command in both output formats
+#[derive(Debug)]
+pub struct Report {
+    pub text: String,
+    pub json: Value,
+}
+
+impl Report {
+    pub fn new(text: impl Into<String>, json: Value) -> Self {
+        Self {
+            text: text.into(),
+            json,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandError {
+    pub message: String,
+    // Extra fields for json output, like per-file errors
+    pub details: Map<String, Value>,
+}
+
+impl CommandError {
+    pub fn with_details(message: impl Into<String>, details: Value) -> Self {
+        Self {
+            message: message.into(),
+            details: match details {
+                Value::Object(details) => details,
+                _ => Map::new(),
+            },
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            details: Map::new(),
+        }
+    }
+}
+
+impl From<PackEncryptionError> for CommandError {
+    fn from(e: PackEncryptionError) -> Self {
+        let details = e.details();
+        let mut message = format!("Pack encryption error: {}", error_chain(&e));
+        if let PackEncryptionError::AlreadyEncrypted(_) = e {
+            message.push_str(". Use --force to encrypt it anyway");
+        }
+        Self::with_details(message, details)
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<BuildError> for CommandError {
+    fn from(e: BuildError) -> Self {
+        match e {
+            BuildError::EncryptionError(e) => Self::from(e),
+            e => Self::from(format!("Build error: {}", error_chain(&e))),
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl From<WatchError> for CommandError {
+    fn from(e: WatchError) -> Self {
+        match e {
+            WatchError::EncryptionError(e) => Self::from(e),
+            e => Self::from(format!("Watch error: {}", error_chain(&e))),
+        }
+    }
+}
+
+// Output closed by the reader, like in `kotik ... | head`, means nobody
+// needs the rest of it, so it's a clean exit and not a panic
+pub fn print_line(line: impl Display) {
+    if let Err(e) = writeln!(stdout().lock(), "{}", line) {
+        if e.kind() == ErrorKind::BrokenPipe {
+            exit(0);
+        }
+        eprintln!("Can't write output: {}", e);
+        exit(1);
+    }
+}
+
+pub fn print_result(
+    context: &Context,
+    command: &str,
+    result: Result<Report, CommandError>,
+    duration: Duration,
+) {
+    let mut document = Map::new();
+    document.insert(String::from("command"), json!(command));
+    document.insert(String::from("ok"), json!(result.is_ok()));
+
+    match result {
+        Ok(report) => match context.output {
+            OutputFormat::Text => {
+                if !report.text.is_empty() {
+                    print_line(report.text)
+                }
+                return;
+            }
+            OutputFormat::Json => match report.json {
+                Value::Object(fields) => document.extend(fields),
+                Value::Null => {}
+                value => {
+                    document.insert(String::from("result"), value);
+                }
+            },
+        },
+        Err(e) => match context.output {
+            OutputFormat::Text => {
+                print_line(format_args!("{}{}{}", RED, e.message, RESET));
+                return;
+            }
+            OutputFormat::Json => {
+                document.insert(String::from("error"), json!(e.message));
+                document.extend(e.details);
+            }
+        },
+    }
+
+    document.insert(
+        String::from("duration_ms"),
+        json!(duration.as_millis() as u64),
+    );
+    print_line(Value::Object(document));
+}
@@ -0,0 +1,86 @@
+use crate::packs::progress::{ProgressCallback, ProgressEvent, ProgressStage};
+use crate::utils::output::{Context, OutputFormat};
+use std::io::{stderr, IsTerminal, Write};
+use std::sync::Mutex;
+
+// Progress bar for text output. It's drawn on stderr, so it never
+// gets into redirected output, and only when stderr is a terminal
+
+const WIDTH: usize = 30;
+
+#[derive(Default)]
+struct State {
+    total: usize,
+    done: usize,
+    failed: usize,
+    // Redrawing on every file is too slow for big packs
+    drawn_cells: Option<usize>,
+}
+
+fn stage_name(stage: ProgressStage) -> &'static str {
+    match stage {
+        ProgressStage::Minify => "Minifying",
+        ProgressStage::Encrypt => "Encrypting",
+        ProgressStage::Decrypt => "Decrypting",
+    }
+}
+
+fn draw(stage: ProgressStage, state: &mut State, force: bool) {
+    let processed = state.done + state.failed;
+    let cells = (processed * WIDTH)
+        .checked_div(state.total)
+        .unwrap_or(WIDTH);
+    if !force && state.drawn_cells == Some(cells) {
+        return;
+    }
+    state.drawn_cells = Some(cells);
+
+    let mut line = format!(
+        "\r{:<10} [{}{}] {}/{}",
+        stage_name(stage),
+        "#".repeat(cells),
+        "-".repeat(WIDTH - cells),
+        processed,
+        state.total
+    );
+    if state.failed > 0 {
+        line.push_str(&format!(", {} failed", state.failed));
+    }
+
+    let mut stderr = stderr().lock();
+    let _ = stderr.write_all(line.as_bytes());
+    let _ = stderr.flush();
+}
+
+// Returns None when progress shouldn't be shown at all
+pub fn progress_bar(context: &Context) -> Option<ProgressCallback> {
+    if context.output != OutputFormat::Text || !stderr().is_terminal() {
+        return None;
+    }
+
+    let state = Mutex::new(State::default());
+    Some(ProgressCallback::new(move |event| {
+        let mut state = state.lock().unwrap();
+        match event {
+            ProgressEvent::Started { stage, total } => {
+                *state = State {
+                    total: *total,
+                    ..State::default()
+                };
+                draw(*stage, &mut state, true);
+            }
+            ProgressEvent::FileDone { stage, .. } => {
+                state.done += 1;
+                draw(*stage, &mut state, false);
+            }
+            ProgressEvent::FileFailed { stage, .. } => {
+                state.failed += 1;
+                draw(*stage, &mut state, true);
+            }
+            ProgressEvent::Finished { stage, .. } => {
+                draw(*stage, &mut state, true);
+                eprintln!();
+            }
+        }
+    }))
+}
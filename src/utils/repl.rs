@@ -0,0 +1,160 @@
+use crate::utils::cli::{command_names, handle_user_input, has_key_argument};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::validate::Validator;
+use rustyline::{Config, Context, Editor, Helper};
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+// Interactive mode with line editing, persistent history
+// and completion of command names and paths.
+// Lines with keys are never saved to history
+
+const HISTORY_FILE: &str = ".kotik_history";
+const HISTORY_SIZE: usize = 1000;
+
+struct KotikHelper {
+    filename_completer: FilenameCompleter,
+}
+
+impl Completer for KotikHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before_cursor = &line[..pos];
+        let word_start = before_cursor.len() - before_cursor.trim_start().len();
+
+        // First word is a command, everything after it are paths
+        if before_cursor[word_start..].contains(char::is_whitespace) {
+            return self.filename_completer.complete(line, pos, ctx);
+        }
+
+        let prefix = &before_cursor[word_start..];
+        let candidates = command_names()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| Pair {
+                display: name.to_owned(),
+                replacement: format!("{} ", name),
+            })
+            .collect();
+        Ok((word_start, candidates))
+    }
+}
+
+impl Hinter for KotikHelper {
+    type Hint = String;
+}
+
+impl Highlighter for KotikHelper {}
+
+impl Validator for KotikHelper {}
+
+impl Helper for KotikHelper {}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+// History is readable only by the user, even if it was created by older version
+#[cfg(unix)]
+fn create_history_file(path: &Path) -> std::io::Result<()> {
+    use std::fs::{OpenOptions, Permissions};
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn create_history_file(_: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+// Older versions saved every line, so keys are removed from loaded history
+fn remove_keys_from_history(
+    editor: &mut Editor<KotikHelper, DefaultHistory>,
+    path: &Path,
+) -> rustyline::Result<()> {
+    let lines = editor
+        .history()
+        .iter()
+        .filter(|line| !has_key_argument(line))
+        .cloned()
+        .collect::<Vec<_>>();
+    if lines.len() == editor.history().len() {
+        return Ok(());
+    }
+
+    editor.clear_history()?;
+    if lines.is_empty() {
+        // Empty history isn't saved, so the file is emptied right away
+        File::create(path)?;
+        return Ok(());
+    }
+    for line in lines {
+        editor.add_history_entry(line)?;
+    }
+    editor.save_history(path)
+}
+
+pub fn run_repl() -> rustyline::Result<()> {
+    let config = Config::builder().max_history_size(HISTORY_SIZE)?.build();
+    let mut editor: Editor<KotikHelper, DefaultHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(KotikHelper {
+        filename_completer: FilenameCompleter::new(),
+    }));
+
+    let history_path = history_path();
+    if let Some(path) = &history_path {
+        if let Err(e) = create_history_file(path) {
+            eprintln!("Can't create history file: {}", e);
+        }
+        // There is no history on the first launch
+        let _ = editor.load_history(path);
+        if let Err(e) = remove_keys_from_history(&mut editor, path) {
+            eprintln!("Can't remove keys from history: {}", e);
+        }
+    }
+
+    println!("Enter command or use 'help' for available commands:");
+    loop {
+        let input = match editor.readline("> ") {
+            Ok(input) => input,
+            // Ctrl-C clears current line
+            Err(ReadlineError::Interrupted) => continue,
+            // Ctrl-D
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+
+        if !has_key_argument(&input) {
+            editor.add_history_entry(input.as_str())?;
+        }
+        if let Some(path) = &history_path {
+            // History is saved after every command, since exit command
+            // and Ctrl-C don't give a chance to do it later
+            if let Err(e) = editor.save_history(path) {
+                eprintln!("Can't save history: {}", e);
+            }
+        }
+
+        handle_user_input(&input);
+    }
+
+    Ok(())
+}
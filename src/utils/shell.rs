@@ -0,0 +1,133 @@
+use std::fmt::{Display, Formatter};
+
+// Shell-like splitting of interactive input, so paths with spaces
+// can be written as "My Pack" or My\ Pack.
+// Backslash escapes only whitespaces, quotes and itself, otherwise it's
+// kept as is, so Windows paths like C:\packs\my_pack work without quotes
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShellSplitError {
+    UnterminatedQuote(char),
+}
+
+impl Display for ShellSplitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShellSplitError::UnterminatedQuote(quote) => {
+                write!(f, "Missing closing quote {}", quote)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShellSplitError {}
+
+fn is_escapable(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | '\'' | '\\')
+}
+
+pub fn split_args(input: &str) -> Result<Vec<String>, ShellSplitError> {
+    let mut args = Vec::new();
+    // None between arguments, so "" is still an argument
+    let mut current: Option<String> = None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                // Everything is literal in single quotes
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(ShellSplitError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                            arg.extend(chars.next());
+                        }
+                        Some(c) => arg.push(c),
+                        None => return Err(ShellSplitError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' if chars.peek().is_some_and(|c| is_escapable(*c)) => {
+                current.get_or_insert_with(String::new).extend(chars.next());
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str) -> Vec<String> {
+        split_args(input).unwrap()
+    }
+
+    #[test]
+    fn whitespaces() {
+        assert_eq!(split("  inspect \t pack  "), ["inspect", "pack"]);
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(split(r#"inspect "My Pack""#), ["inspect", "My Pack"]);
+        assert_eq!(split("inspect 'My Pack'"), ["inspect", "My Pack"]);
+        assert_eq!(split(r#"a "" ''"#), ["a", "", ""]);
+        // Quoted and unquoted parts are one argument
+        assert_eq!(split(r#"packs/"My Pack"/rp"#), ["packs/My Pack/rp"]);
+        assert_eq!(split(r#"'it"s' "it's""#), [r#"it"s"#, "it's"]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(split(r"inspect My\ Pack"), ["inspect", "My Pack"]);
+        assert_eq!(split(r#"\"a\" \\"#), [r#""a""#, r"\"]);
+        assert_eq!(split(r#""say \"hi\" \\ \n""#), [r#"say "hi" \ \n"#]);
+        // Nothing is escaped in single quotes
+        assert_eq!(split(r"'a\ b\'"), [r"a\ b\"]);
+        // Windows paths
+        assert_eq!(
+            split(r"inspect C:\packs\my_pack"),
+            ["inspect", r"C:\packs\my_pack"]
+        );
+        assert_eq!(split(r"a\"), [r"a\"]);
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        assert_eq!(
+            split_args(r#"inspect "My Pack"#),
+            Err(ShellSplitError::UnterminatedQuote('"'))
+        );
+        assert_eq!(
+            split_args("inspect 'My Pack"),
+            Err(ShellSplitError::UnterminatedQuote('\''))
+        );
+        assert_eq!(
+            split_args(r#"inspect "My Pack\""#),
+            Err(ShellSplitError::UnterminatedQuote('"'))
+        );
+    }
+}
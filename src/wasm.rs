@@ -0,0 +1,96 @@
+// WebAssembly module for browsers, built with wasm-pack:
+//   wasm-pack build --target web --no-default-features --features wasm
+// Everything works in memory and on a single thread, pack files never leave the page
+
+use crate::packs::archive::{encrypt_archive_storage, EncryptedPack};
+use crate::packs::contents::parse_contents_header;
+use crate::packs::encryption::decrypt::{decrypt_contents, decrypt_storage, DecryptOptions};
+use crate::packs::encryption::encrypt::EncryptOptions;
+use crate::packs::pack_encryption::error_chain;
+use crate::packs::storage::ZipStorage;
+use crate::utils::cipher::{aes256_cbf8_encrypt, aes256_cfb8_decrypt, generate_random_key};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+fn js_error(error: impl std::error::Error) -> JsError {
+    JsError::new(&error_chain(&error))
+}
+
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(value).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = randomKey)]
+pub fn random_key() -> String {
+    generate_random_key()
+}
+
+// Raw AES-256-CFB8, the same as for every file of the pack
+#[wasm_bindgen(js_name = encryptBytes)]
+pub fn encrypt_bytes(key: &str, data: &[u8]) -> Result<Vec<u8>, JsError> {
+    aes256_cbf8_encrypt(key, data.to_vec()).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = decryptBytes)]
+pub fn decrypt_bytes(key: &str, data: &[u8]) -> Result<Vec<u8>, JsError> {
+    aes256_cfb8_decrypt(key, data.to_vec()).map_err(js_error)
+}
+
+#[wasm_bindgen]
+pub struct EncryptedArchive {
+    archive: Vec<u8>,
+    packs: Vec<EncryptedPack>,
+}
+
+#[wasm_bindgen]
+impl EncryptedArchive {
+    // Encrypted .mcpack/.mcaddon
+    #[wasm_bindgen(getter)]
+    pub fn archive(&self) -> Vec<u8> {
+        self.archive.clone()
+    }
+
+    // Path, uuid, key and report of every pack, as in json output of the CLI
+    #[wasm_bindgen(getter)]
+    pub fn packs(&self) -> Result<JsValue, JsError> {
+        to_js(&self.packs)
+    }
+}
+
+// Every pack of .mcpack/.mcaddon gets its own random key
+#[wasm_bindgen(js_name = encryptArchive)]
+pub fn encrypt_archive(archive: &[u8], minify: bool) -> Result<EncryptedArchive, JsError> {
+    let storage = ZipStorage::from_bytes(archive).map_err(js_error)?;
+    let options = EncryptOptions {
+        minify,
+        ..Default::default()
+    };
+    let packs = encrypt_archive_storage(&storage, &options).map_err(js_error)?;
+
+    Ok(EncryptedArchive {
+        archive: storage.to_bytes().map_err(js_error)?,
+        packs,
+    })
+}
+
+// Archive must have the pack in its root, like .mcpack
+#[wasm_bindgen(js_name = decryptArchive)]
+pub fn decrypt_archive(archive: &[u8], key: &str) -> Result<Vec<u8>, JsError> {
+    let storage = ZipStorage::from_bytes(archive).map_err(js_error)?;
+    decrypt_storage(key, &storage, &DecryptOptions::default()).map_err(js_error)?;
+    storage.to_bytes().map_err(js_error)
+}
+
+// UUID from header of encrypted contents.json, no key needed
+#[wasm_bindgen(js_name = contentsUuid)]
+pub fn contents_uuid(contents: &[u8]) -> Result<String, JsError> {
+    let header = parse_contents_header(contents).map_err(js_error)?;
+    Ok(header.uuid)
+}
+
+// Decrypted contents.json with paths and keys of pack files
+#[wasm_bindgen(js_name = decryptContents)]
+pub fn decrypt_contents_json(key: &str, contents: &[u8]) -> Result<JsValue, JsError> {
+    let (_, root, _) = decrypt_contents(key, contents).map_err(js_error)?;
+    to_js(&root)
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{from_hex, KEY};
+use kotik::utils::cipher::{
+    aes256_cbf8_encrypt, aes256_cfb8_decrypt, generate_random_key, CipherError,
+};
+
+// Known answers are produced by OpenSSL, IV is the first 16 bytes of the key:
+// openssl enc -aes-256-cfb8 -K <hex of key> -iv <hex of key[..16]>
+const VECTORS: [(&str, &[u8], &str); 6] = [
+    (
+        KEY,
+        b"Hello, Minecraft Bedrock!",
+        "b041bfb548cd177536ef8f08f75e5f9f19e29cba262d39cf1b",
+    ),
+    (
+        KEY,
+        b"{\"format_version\":2}",
+        "8336b59ea6b5855870ebedc42dbc37ae324a0325",
+    ),
+    (
+        KEY,
+        &SEQUENCE,
+        "f8d45eb4e8bdbcf2711946687e515f78aa0fd9910ad3d8e9ba7057366ca298b6\
+         1161a60b7b5ff30eb6f051754347a800f7238c7f62987a8728b44bcddf0a90e9",
+    ),
+    (
+        "s5s5ejuDru4uchuF2drUFuthaspAbepE",
+        b"Hello, Minecraft Bedrock!",
+        "553685e931745074bc17dd37113b95e35501bcb5360101275f",
+    ),
+    (
+        "s5s5ejuDru4uchuF2drUFuthaspAbepE",
+        b"{\"format_version\":2}",
+        "66dfd86e9bb6b43c54243d131a9237e2386c72d3",
+    ),
+    (
+        "s5s5ejuDru4uchuF2drUFuthaspAbepE",
+        &SEQUENCE,
+        "1d61a0e47dc96711b5c177490e92ff057cac37a65be8a01b7993ab0cb1f2ae12\
+         43c32976556352cac4b6f56ad04d2ec164a693cc37d4ac990148030b19735743",
+    ),
+];
+
+// Bytes 0x00..0x3F
+const SEQUENCE: [u8; 64] = {
+    let mut bytes = [0u8; 64];
+    let mut i = 0;
+    while i < 64 {
+        bytes[i] = i as u8;
+        i += 1;
+    }
+    bytes
+};
+
+#[test]
+fn encrypt_matches_known_answers() {
+    for (key, plaintext, ciphertext) in VECTORS {
+        let encrypted = aes256_cbf8_encrypt(key, plaintext.to_vec()).unwrap();
+        assert_eq!(encrypted, from_hex(ciphertext), "key {}", key);
+    }
+}
+
+#[test]
+fn decrypt_matches_known_answers() {
+    for (key, plaintext, ciphertext) in VECTORS {
+        let decrypted = aes256_cfb8_decrypt(key, from_hex(ciphertext)).unwrap();
+        assert_eq!(decrypted, plaintext, "key {}", key);
+    }
+}
+
+#[test]
+fn cfb8_keeps_length() {
+    // Stream mode, so no padding even for empty and odd lengths
+    for length in [0, 1, 15, 16, 17, 1000] {
+        let encrypted = aes256_cbf8_encrypt(KEY, vec![7; length]).unwrap();
+        assert_eq!(encrypted.len(), length);
+    }
+}
+
+#[test]
+fn invalid_key_length_is_rejected() {
+    for key in ["", "short", "0123456789abcdef0123456789abcdef0"] {
+        assert!(matches!(
+            aes256_cbf8_encrypt(key, b"data".to_vec()),
+            Err(CipherError::InvalidKeyLength(length)) if length == key.len()
+        ));
+        assert!(matches!(
+            aes256_cfb8_decrypt(key, b"data".to_vec()),
+            Err(CipherError::InvalidKeyLength(_))
+        ));
+    }
+}
+
+#[test]
+fn random_key_is_usable() {
+    let key = generate_random_key();
+    assert_eq!(key.len(), 32);
+    assert!(key.chars().all(|c| c.is_ascii_alphanumeric()));
+    assert_ne!(key, generate_random_key());
+
+    let encrypted = aes256_cbf8_encrypt(&key, b"round trip".to_vec()).unwrap();
+    assert_eq!(aes256_cfb8_decrypt(&key, encrypted).unwrap(), b"round trip");
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::{fixture_path, UUID};
+use kotik::packs::contents::{
+    generate_contents_header, generate_contents_root, parse_contents_header, CONTENTS_HEADER_SIZE,
+    CONTENTS_MAGIC,
+};
+use std::fs;
+use std::path::{PathBuf, MAIN_SEPARATOR_STR};
+
+#[test]
+fn header_matches_golden_file() {
+    let golden = fs::read(fixture_path("contents_header.bin")).unwrap();
+    assert_eq!(golden.len(), CONTENTS_HEADER_SIZE);
+    assert_eq!(generate_contents_header(UUID).unwrap(), golden);
+}
+
+#[test]
+fn header_round_trip() {
+    let header = parse_contents_header(&generate_contents_header(UUID).unwrap()).unwrap();
+    assert_eq!(header.version, 0);
+    assert_eq!(header.magic, CONTENTS_MAGIC);
+    assert_eq!(header.uuid, UUID);
+}
+
+#[test]
+fn header_uuid_length_limit() {
+    // 256 bytes minus 16 bytes of version, magic and padding, minus length byte
+    let longest = "a".repeat(239);
+    let header = generate_contents_header(&longest).unwrap();
+    assert_eq!(header.len(), CONTENTS_HEADER_SIZE);
+    assert_eq!(parse_contents_header(&header).unwrap().uuid, longest);
+
+    for length in [240, 255, 256, 1000] {
+        assert!(generate_contents_header(&"a".repeat(length)).is_err());
+    }
+}
+
+#[test]
+fn empty_uuid_header() {
+    let header = generate_contents_header("").unwrap();
+    assert_eq!(header.len(), CONTENTS_HEADER_SIZE);
+    assert_eq!(parse_contents_header(&header).unwrap().uuid, "");
+}
+
+#[test]
+fn malformed_header_is_rejected() {
+    let header = generate_contents_header(UUID).unwrap();
+    assert!(parse_contents_header(&header[..CONTENTS_HEADER_SIZE - 1]).is_err());
+
+    // Length byte pointing past the header
+    let mut broken = header.clone();
+    broken[0x10] = 0xFF;
+    assert!(parse_contents_header(&broken).is_err());
+}
+
+#[test]
+fn root_keys_only_for_encrypted_files() {
+    let paths = [
+        "manifest.json",
+        "pack_icon.png",
+        "texts/en_US.lang",
+        "textures/",
+        "textures/blocks/stone.png",
+        "entity/sample.entity.json",
+    ]
+    .map(|path| PathBuf::from(path.replace('/', MAIN_SEPARATOR_STR)));
+
+    let root = generate_contents_root(&paths);
+    let keyed = root
+        .iter()
+        .map(|item| (item.path.as_str(), item.key.is_some()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keyed,
+        [
+            ("manifest.json", false),
+            ("pack_icon.png", false),
+            ("texts/en_US.lang", false),
+            ("textures/", false),
+            ("textures/blocks/stone.png", true),
+            ("entity/sample.entity.json", true),
+        ]
+    );
+
+    // Every file gets its own key
+    let keys = root
+        .iter()
+        .filter_map(|item| item.key.as_deref())
+        .collect::<Vec<_>>();
+    assert!(keys.iter().all(|key| key.len() == 32));
+    assert_ne!(keys[0], keys[1]);
+}
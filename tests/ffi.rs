@@ -0,0 +1,154 @@
+mod common;
+
+use common::{copy_fixture, fixture_path, read_tree, FIXTURE_PACK, KEY};
+use kotik::ffi::{
+    kotik_decrypt_dir, kotik_encrypt_buffer, kotik_encrypt_dir, kotik_free_buffer,
+    kotik_last_error_message, KotikStatus, KOTIK_FORCE,
+};
+use kotik::packs::encryption::decrypt::{decrypt_storage, DecryptOptions};
+use kotik::packs::storage::{PackStorage, ZipStorage};
+use std::ffi::{CStr, CString};
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::ptr;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+fn c_string(value: &str) -> CString {
+    CString::new(value).unwrap()
+}
+
+fn c_path(path: &Path) -> CString {
+    c_string(path.to_str().unwrap())
+}
+
+fn last_error() -> Option<String> {
+    let message = kotik_last_error_message();
+    (!message.is_null()).then(|| {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+fn fixture_zip() -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in read_tree(&fixture_path(FIXTURE_PACK)) {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn encrypt_and_decrypt_dir() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+    let (key, path) = (c_string(KEY), c_path(pack.path()));
+
+    let status = unsafe { kotik_encrypt_dir(key.as_ptr(), path.as_ptr(), 0) };
+    assert_eq!(status, KotikStatus::Ok);
+    assert!(last_error().is_none());
+    assert_ne!(read_tree(pack.path()), original);
+
+    let status = unsafe { kotik_encrypt_dir(key.as_ptr(), path.as_ptr(), 0) };
+    assert_eq!(status, KotikStatus::AlreadyEncrypted);
+    assert!(last_error().is_some());
+
+    let status = unsafe { kotik_decrypt_dir(key.as_ptr(), path.as_ptr()) };
+    assert_eq!(status, KotikStatus::Ok);
+    let mut decrypted = read_tree(pack.path());
+    decrypted.remove("contents.json");
+    assert_eq!(decrypted, original);
+
+    let status = unsafe { kotik_encrypt_dir(key.as_ptr(), path.as_ptr(), 0) };
+    assert_eq!(status, KotikStatus::Ok);
+    let status = unsafe { kotik_encrypt_dir(key.as_ptr(), path.as_ptr(), KOTIK_FORCE) };
+    assert_eq!(status, KotikStatus::Ok);
+}
+
+#[test]
+fn encrypt_buffer() {
+    let archive = fixture_zip();
+    let key = c_string(KEY);
+    let (mut out_data, mut out_len) = (ptr::null_mut(), 0);
+
+    let status = unsafe {
+        kotik_encrypt_buffer(
+            key.as_ptr(),
+            archive.as_ptr(),
+            archive.len(),
+            0,
+            &mut out_data,
+            &mut out_len,
+        )
+    };
+    assert_eq!(status, KotikStatus::Ok, "{:?}", last_error());
+    assert!(!out_data.is_null());
+
+    let encrypted = unsafe { std::slice::from_raw_parts(out_data, out_len) }.to_vec();
+    unsafe { kotik_free_buffer(out_data, out_len) };
+
+    let storage = ZipStorage::from_bytes(&encrypted).unwrap();
+    decrypt_storage(KEY, &storage, &DecryptOptions::default()).unwrap();
+    for (name, content) in read_tree(&fixture_path(FIXTURE_PACK)) {
+        assert_eq!(storage.read(Path::new(&name)).unwrap(), content, "{}", name);
+    }
+}
+
+#[test]
+fn invalid_arguments() {
+    let path = c_string("pack");
+    let status = unsafe { kotik_encrypt_dir(ptr::null(), path.as_ptr(), 0) };
+    assert_eq!(status, KotikStatus::InvalidArgument);
+    assert_eq!(last_error().unwrap(), "key is null");
+
+    let short_key = c_string("short");
+    let status = unsafe { kotik_decrypt_dir(short_key.as_ptr(), path.as_ptr()) };
+    assert_eq!(status, KotikStatus::InvalidKey);
+    assert_eq!(last_error().unwrap(), "Key must be 32 bytes long, got 5");
+
+    let key = c_string(KEY);
+    let (mut out_data, mut out_len) = (ptr::null_mut(), 0);
+    let garbage = b"not a zip archive";
+    let status = unsafe {
+        kotik_encrypt_buffer(
+            key.as_ptr(),
+            garbage.as_ptr(),
+            garbage.len(),
+            0,
+            &mut out_data,
+            &mut out_len,
+        )
+    };
+    assert_eq!(status, KotikStatus::ArchiveError);
+    assert!(out_data.is_null());
+    assert_eq!(out_len, 0);
+}
+
+#[test]
+fn missing_pack() {
+    let temp = tempfile::TempDir::new().unwrap();
+    let (key, path) = (c_string(KEY), c_path(&temp.path().join("missing")));
+
+    let status = unsafe { kotik_encrypt_dir(key.as_ptr(), path.as_ptr(), 0) };
+    assert_eq!(status, KotikStatus::ManifestError);
+    assert!(last_error().is_some());
+
+    let status = unsafe { kotik_decrypt_dir(key.as_ptr(), path.as_ptr()) };
+    assert_eq!(status, KotikStatus::IoError);
+}
+
+#[test]
+fn committed_header_is_up_to_date() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/kotik.h"));
+    let committed =
+        std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("include/kotik.h"))
+            .unwrap();
+    assert!(
+        generated == committed,
+        "include/kotik.h is outdated, run KOTIK_UPDATE_HEADER=1 cargo build --features ffi"
+    );
+}
@@ -0,0 +1,108 @@
+use kotik::utils::jsonc::parse_jsonc;
+use serde_json::json;
+
+// Regression tests for comment handling, which used to be a separate
+// clear_comments pass over the raw text
+
+#[test]
+fn comment_markers_inside_strings_are_kept() {
+    let value = parse_jsonc(
+        r#"{
+            "url": "http://example.com/*not a comment*/",
+            "path": "textures//blocks", // real comment
+            "glob": "/*"
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        value,
+        json!({
+            "url": "http://example.com/*not a comment*/",
+            "path": "textures//blocks",
+            "glob": "/*"
+        })
+    );
+}
+
+#[test]
+fn escaped_quotes_dont_end_strings() {
+    let value = parse_jsonc(r#"{"text": "say \"// hi\" /* */", "slash": "\\"} // end"#).unwrap();
+    assert_eq!(value, json!({"text": "say \"// hi\" /* */", "slash": "\\"}));
+}
+
+#[test]
+fn block_comments_across_lines() {
+    let value =
+        parse_jsonc("{\n  /* first\n     \"hidden\": 1,\n  */ \"shown\": /* inline */ 2\n}")
+            .unwrap();
+    assert_eq!(value, json!({"shown": 2}));
+}
+
+#[test]
+fn comment_at_end_of_file_without_newline() {
+    assert_eq!(parse_jsonc("[1, 2] // done").unwrap(), json!([1, 2]));
+    assert_eq!(parse_jsonc("[1, 2] /**/").unwrap(), json!([1, 2]));
+}
+
+#[test]
+fn comments_separate_tokens() {
+    assert_eq!(parse_jsonc("[1/**/,2]").unwrap(), json!([1, 2]));
+    assert_eq!(
+        parse_jsonc("[true//x\n,null]").unwrap(),
+        json!([true, null])
+    );
+}
+
+#[test]
+fn unterminated_block_comment_reports_position() {
+    let error = parse_jsonc("{\n  \"a\": 1 /* never closed\n}").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 10));
+}
+
+#[test]
+fn single_slash_is_an_error() {
+    assert!(parse_jsonc("{\"a\": 1 / 2}").is_err());
+}
+
+#[test]
+fn trailing_commas_and_bom() {
+    let value = parse_jsonc("\u{FEFF}{\"a\": [1, 2,], \"b\": {\"c\": 3,},}").unwrap();
+    assert_eq!(value, json!({"a": [1, 2], "b": {"c": 3}}));
+}
+
+#[test]
+fn key_order_is_preserved() {
+    let value = parse_jsonc(r#"{"z": 1, "a": 2, "m": 3}"#).unwrap();
+    let keys = value.as_object().unwrap().keys().collect::<Vec<_>>();
+    assert_eq!(keys, ["z", "a", "m"]);
+}
+
+#[test]
+fn floats_survive_minification() {
+    // Found by fuzzing, without exact float parsing these changed by one ulp
+    for source in [
+        "220e86",
+        "2222222222222222222222222222222222222222.5",
+        "0.1",
+        "1e-300",
+    ] {
+        let value = parse_jsonc(source).unwrap();
+        assert_eq!(
+            parse_jsonc(&value.to_string()).unwrap(),
+            value,
+            "{}",
+            source
+        );
+    }
+}
+
+#[test]
+fn only_ascii_control_characters_are_rejected_in_strings() {
+    // Found by fuzzing, DEL and C1 controls are valid in json strings
+    assert_eq!(
+        parse_jsonc("\"\u{7F}\u{85}\"").unwrap(),
+        json!("\u{7F}\u{85}")
+    );
+    assert!(parse_jsonc("\"\u{1}\"").is_err());
+    assert_eq!(parse_jsonc("\"a\tb\"").unwrap(), json!("a\tb"));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{fixture_path, read_tree, FIXTURE_PACK, KEY, UUID};
+use kotik::packs::contents::parse_contents_header;
+use kotik::packs::encryption::decrypt::{decrypt_contents, DecryptOptions};
+use kotik::packs::encryption::encrypt::EncryptOptions;
+use kotik::packs::encryption::memory::{decrypt_in_memory, encrypt_in_memory};
+use kotik::packs::manifest::{parse_manifest, Manifest};
+use kotik::packs::pack_encryption::PackEncryptionError;
+use std::collections::BTreeMap;
+
+// Fixture files without manifest.json, and the manifest itself
+fn generated_pack() -> (BTreeMap<String, Vec<u8>>, Manifest) {
+    let mut files = read_tree(&fixture_path(FIXTURE_PACK));
+    let manifest = files.remove("manifest.json").unwrap();
+    let manifest = parse_manifest(&String::from_utf8(manifest).unwrap()).unwrap();
+    (files, manifest)
+}
+
+#[test]
+fn encrypted_files_include_contents_and_manifest() {
+    let (files, manifest) = generated_pack();
+
+    let encrypted =
+        encrypt_in_memory(KEY, files.clone(), &manifest, &EncryptOptions::default()).unwrap();
+
+    let header = parse_contents_header(&encrypted["contents.json"]).unwrap();
+    assert_eq!(header.uuid, UUID);
+    let (_, root, _) = decrypt_contents(KEY, &encrypted["contents.json"]).unwrap();
+    assert!(root.content.iter().any(|item| item.path == "textures/"));
+
+    let manifest_json =
+        parse_manifest(std::str::from_utf8(&encrypted["manifest.json"]).unwrap()).unwrap();
+    assert_eq!(manifest_json.header.uuid, UUID);
+
+    for (path, content) in &files {
+        let plain = path == "pack_icon.png" || path.starts_with("texts/");
+        assert_eq!(&encrypted[path] == content, plain, "{}", path);
+    }
+}
+
+#[test]
+fn in_memory_round_trip() {
+    let (files, manifest) = generated_pack();
+
+    let encrypted =
+        encrypt_in_memory(KEY, files.clone(), &manifest, &EncryptOptions::default()).unwrap();
+    let mut decrypted = decrypt_in_memory(KEY, encrypted, &DecryptOptions::default()).unwrap();
+
+    assert!(decrypted.remove("contents.json").is_some());
+    assert!(decrypted.remove("manifest.json").is_some());
+    assert_eq!(decrypted, files);
+}
+
+#[test]
+fn windows_separators_are_normalized() {
+    let (mut files, manifest) = generated_pack();
+    let stone = files.remove("textures/blocks/stone.png").unwrap();
+    files.insert(String::from("textures\\blocks\\stone.png"), stone);
+
+    let encrypted = encrypt_in_memory(KEY, files, &manifest, &EncryptOptions::default()).unwrap();
+    assert!(encrypted.contains_key("textures/blocks/stone.png"));
+    assert!(!encrypted.contains_key("textures\\blocks\\stone.png"));
+}
+
+#[test]
+fn paths_outside_of_pack_are_rejected() {
+    let (_, manifest) = generated_pack();
+
+    for path in ["../skin.png", "/skin.png", "textures/../../skin.png"] {
+        let files = BTreeMap::from([(String::from(path), vec![0u8; 4])]);
+        assert!(
+            matches!(
+                encrypt_in_memory(KEY, files, &manifest, &EncryptOptions::default()),
+                Err(PackEncryptionError::DataCollectionError(_))
+            ),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn invalid_manifest_is_rejected() {
+    let (files, mut manifest) = generated_pack();
+    manifest.modules.clear();
+
+    assert!(matches!(
+        encrypt_in_memory(KEY, files, &manifest, &EncryptOptions::default()),
+        Err(PackEncryptionError::ManifestError(_))
+    ));
+}
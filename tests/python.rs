@@ -0,0 +1,171 @@
+mod common;
+
+use common::{copy_fixture, fixture_path, read_tree, FIXTURE_PACK, KEY, UUID};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::Write;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+// Runs script with kotik module and given variables, panics with Python traceback
+fn run_python(script: &CStr, variables: impl FnOnce(&Bound<'_, PyDict>)) {
+    Python::initialize();
+    Python::attach(|py| {
+        let module = PyModule::new(py, "kotik").unwrap();
+        kotik::python::kotik(&module).unwrap();
+
+        let globals = PyDict::new(py);
+        globals.set_item("kotik", module).unwrap();
+        globals.set_item("KEY", KEY).unwrap();
+        globals.set_item("UUID", UUID).unwrap();
+        variables(&globals);
+
+        if let Err(e) = py.run(script, Some(&globals), None) {
+            e.display(py);
+            panic!("Python script failed: {}", e);
+        }
+    });
+}
+
+#[test]
+fn encrypt_and_decrypt_dir() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    run_python(
+        cr#"
+report = kotik.encrypt(path, KEY)
+assert report["uuid"] == UUID, report
+assert report["encrypted_files"] > 0
+
+assert kotik.inspect(path)["encrypted"]
+assert kotik.verify(path, KEY)["missing"] == []
+
+report = kotik.decrypt(path, KEY, jobs=1)
+assert report["decrypted_files"] > 0
+"#,
+        |globals| globals.set_item("path", pack.path()).unwrap(),
+    );
+
+    let mut decrypted = read_tree(pack.path());
+    decrypted.remove("contents.json");
+    assert_eq!(decrypted, original);
+}
+
+#[test]
+fn errors_are_mapped_to_exceptions() {
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    run_python(
+        cr#"
+kotik.encrypt(path, KEY)
+
+try:
+    kotik.encrypt(path, KEY)
+    raise AssertionError("encrypted twice")
+except kotik.AlreadyEncryptedError as e:
+    assert isinstance(e, kotik.KotikError)
+    assert e.details["evidence"], e.details
+
+try:
+    kotik.decrypt(path, "short")
+    raise AssertionError("short key accepted")
+except kotik.InvalidKeyError as e:
+    assert str(e) == "Key must be 32 bytes long, got 5"
+
+try:
+    kotik.decrypt(path, "abcdefghijklmnopqrstuvwxyz012345")
+    raise AssertionError("wrong key accepted")
+except kotik.ContentsError:
+    pass
+
+try:
+    kotik.encrypt(path + "/missing", KEY)
+    raise AssertionError("missing pack encrypted")
+except kotik.ManifestError:
+    pass
+
+assert len(kotik.random_key()) == 32
+"#,
+        |globals| {
+            globals
+                .set_item("path", pack.path().to_str().unwrap())
+                .unwrap()
+        },
+    );
+}
+
+#[test]
+fn encrypt_and_decrypt_files() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+
+    run_python(
+        cr#"
+encrypted = kotik.encrypt_files(files, KEY)
+assert "contents.json" in encrypted
+# Manifest is kept byte-for-byte
+assert encrypted["manifest.json"] == files["manifest.json"]
+assert any(encrypted[path] != files[path] for path in files)
+
+decrypted = kotik.decrypt_files(encrypted, KEY)
+del decrypted["contents.json"]
+assert decrypted == files
+
+try:
+    kotik.encrypt_files({**files, "../escape.json": b"{}"}, KEY)
+    raise AssertionError("unsafe path accepted")
+except kotik.KotikError:
+    pass
+
+# Like the command line tool, packs are linted unless lint=False
+broken = {**files, "broken.json": b"{"}
+try:
+    kotik.encrypt_files(broken, KEY)
+    raise AssertionError("lint errors ignored")
+except kotik.LintError:
+    pass
+assert "broken.json" in kotik.encrypt_files(broken, KEY, lint=False)
+"#,
+        |globals| {
+            let files = PyDict::new(globals.py());
+            for (path, content) in &original {
+                files
+                    .set_item(path, PyBytes::new(globals.py(), content))
+                    .unwrap();
+            }
+            globals.set_item("files", files).unwrap();
+        },
+    );
+}
+
+#[test]
+fn encrypt_archive() {
+    let temp = tempfile::TempDir::new().unwrap();
+    let archive_path = temp.path().join("pack.mcpack");
+    let mut writer = ZipWriter::new(File::create(&archive_path).unwrap());
+    for (name, content) in read_tree(&fixture_path(FIXTURE_PACK)) {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&content).unwrap();
+    }
+    writer.finish().unwrap();
+
+    run_python(
+        cr#"
+import os
+
+result = kotik.encrypt_archive(path)
+assert len(result["packs"]) == 1, result
+assert len(result["packs"][0]["key"]) == 32
+assert result["archive"] != path and os.path.exists(result["archive"])
+"#,
+        |globals| {
+            globals
+                .set_item("path", archive_path.to_str().unwrap())
+                .unwrap()
+        },
+    );
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use common::{copy_fixture, fixture_path, read_tree, FIXTURE_PACK, KEY, UUID};
+use kotik::packs::contents::DONT_ENCRYPT;
+use kotik::packs::encryption::decrypt::{decrypt, decrypt_contents, DecryptOptions};
+use kotik::packs::encryption::encrypt::{encrypt, EncryptOptions};
+use kotik::packs::pack_encryption::{
+    error_chain, CancellationToken, PackEncryptionError, ProcessingOptions,
+};
+use kotik::packs::progress::{ProgressCallback, ProgressEvent};
+use kotik::packs::verify::verify;
+use kotik::utils::jsonc::parse_jsonc;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+fn is_plain(path: &str) -> bool {
+    DONT_ENCRYPT.iter().any(|pattern| path.starts_with(pattern))
+}
+
+fn encrypt_options() -> EncryptOptions {
+    EncryptOptions {
+        lint: true,
+        ..Default::default()
+    }
+}
+
+fn target(path: &Path) -> std::ffi::OsString {
+    path.as_os_str().to_os_string()
+}
+
+#[test]
+fn encrypt_then_decrypt_restores_every_file() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let report = encrypt(KEY, target(pack.path()), &encrypt_options()).unwrap();
+    assert_eq!(report.uuid, UUID);
+    assert_eq!(report.files, original.len());
+    assert_eq!(
+        report.encrypted_files,
+        original.keys().filter(|path| !is_plain(path)).count()
+    );
+
+    let encrypted = read_tree(pack.path());
+    for (path, content) in &original {
+        if is_plain(path) {
+            assert_eq!(&encrypted[path], content, "{} must be left as is", path);
+        } else {
+            assert_ne!(&encrypted[path], content, "{} must be encrypted", path);
+            assert_eq!(encrypted[path].len(), content.len());
+        }
+    }
+
+    let report = decrypt(KEY, target(pack.path()), &DecryptOptions::default()).unwrap();
+    assert_eq!(report.uuid, UUID);
+
+    let mut decrypted = read_tree(pack.path());
+    // Decrypt leaves decrypted contents.json, the rest must match byte-for-byte
+    assert!(decrypted.remove("contents.json").is_some());
+    assert_eq!(decrypted, original);
+}
+
+#[test]
+fn contents_lists_every_entry() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    encrypt(KEY, target(pack.path()), &encrypt_options()).unwrap();
+
+    let contents = fs::read(pack.path().join("contents.json")).unwrap();
+    let (header, root, _) = decrypt_contents(KEY, &contents).unwrap();
+    assert_eq!(header.uuid, UUID);
+    assert_eq!(root.version, 1);
+
+    for item in &root.content {
+        let is_folder = item.path.ends_with('/');
+        assert_eq!(
+            item.key.is_some(),
+            !is_folder && !is_plain(&item.path),
+            "{}",
+            item.path
+        );
+    }
+
+    let mut listed_files = root
+        .content
+        .iter()
+        .map(|item| item.path.as_str())
+        .filter(|path| !path.ends_with('/'))
+        .collect::<Vec<_>>();
+    listed_files.sort();
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    assert!(listed_files.iter().eq(original.keys()));
+
+    assert!(verify(KEY, &target(pack.path())).unwrap().is_valid());
+}
+
+#[test]
+fn wrong_key_is_rejected() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    encrypt(KEY, target(pack.path()), &encrypt_options()).unwrap();
+
+    let wrong_key = "fedcba9876543210fedcba9876543210";
+    assert!(verify(wrong_key, &target(pack.path())).is_err());
+    assert!(decrypt(wrong_key, target(pack.path()), &DecryptOptions::default()).is_err());
+}
+
+#[test]
+fn encrypted_pack_is_not_encrypted_again() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    encrypt(KEY, target(pack.path()), &encrypt_options()).unwrap();
+    let encrypted = read_tree(pack.path());
+
+    assert!(matches!(
+        encrypt(KEY, target(pack.path()), &EncryptOptions::default()),
+        Err(PackEncryptionError::AlreadyEncrypted(_))
+    ));
+    // Refused encryption doesn't touch files
+    assert_eq!(read_tree(pack.path()), encrypted);
+
+    let options = EncryptOptions {
+        force: true,
+        ..Default::default()
+    };
+    assert!(encrypt(KEY, target(pack.path()), &options).is_ok());
+}
+
+#[test]
+fn minified_pack_round_trip() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let options = EncryptOptions {
+        minify: true,
+        ..encrypt_options()
+    };
+    let report = encrypt(KEY, target(pack.path()), &options).unwrap();
+    assert!(report.minify.is_some());
+
+    decrypt(KEY, target(pack.path()), &DecryptOptions::default()).unwrap();
+    let decrypted = read_tree(pack.path());
+
+    for (path, content) in &original {
+        // manifest.json stays readable, so it's left as is
+        if !path.ends_with(".json") || path == "manifest.json" {
+            assert_eq!(&decrypted[path], content, "{}", path);
+            continue;
+        }
+
+        // Comments and whitespaces are gone, values are the same
+        let minified = String::from_utf8(decrypted[path].clone()).unwrap();
+        let expected = parse_jsonc(&String::from_utf8(content.clone()).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&minified).unwrap(),
+            expected,
+            "{}",
+            path
+        );
+        assert!(!minified.contains('\n'), "{}", path);
+    }
+}
+
+#[test]
+fn cancelled_encryption_can_be_decrypted() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    // Cancelled right after the first encrypted file
+    let cancellation = CancellationToken::default();
+    let token = cancellation.clone();
+    let options = EncryptOptions {
+        processing: ProcessingOptions {
+            jobs: 1,
+            cancellation,
+            ..Default::default()
+        },
+        progress: Some(ProgressCallback::new(move |event| {
+            if let ProgressEvent::FileDone { .. } = event {
+                token.cancel();
+            }
+        })),
+        ..Default::default()
+    };
+
+    let partial = match encrypt(KEY, target(pack.path()), &options) {
+        Err(PackEncryptionError::PartiallyEncrypted(partial)) => partial,
+        result => panic!("unexpected result {:?}", result),
+    };
+    assert!(matches!(*partial.cause, PackEncryptionError::Cancelled(_)));
+    assert!(!partial.unencrypted.is_empty());
+    for path in &partial.unencrypted {
+        assert_eq!(read_tree(pack.path())[path], original[path], "{}", path);
+    }
+
+    // contents.json lists only encrypted files, so decryption restores the pack
+    decrypt(KEY, target(pack.path()), &DecryptOptions::default()).unwrap();
+    let mut decrypted = read_tree(pack.path());
+    decrypted.remove("contents.json");
+    assert_eq!(decrypted, original);
+}
+
+#[test]
+fn invalid_key_leaves_pack_as_is() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let result = encrypt("short", target(pack.path()), &encrypt_options());
+    assert!(matches!(result, Err(PackEncryptionError::CipherError(_))));
+    assert_eq!(read_tree(pack.path()), original);
+}
+
+#[test]
+fn contents_is_written_before_files() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let contents_path = pack.path().join("contents.json");
+    let listed_keys = Arc::new(Mutex::new(Vec::new()));
+
+    let options = EncryptOptions {
+        progress: Some(ProgressCallback::new({
+            let listed_keys = listed_keys.clone();
+            move |event| {
+                if let ProgressEvent::FileDone { .. } = event {
+                    // Every key is already there, even if the process is killed right now
+                    let (_, root, _) =
+                        decrypt_contents(KEY, &fs::read(&contents_path).unwrap()).unwrap();
+                    let keys = root
+                        .content
+                        .iter()
+                        .filter(|item| item.key.is_some())
+                        .count();
+                    listed_keys.lock().unwrap().push(keys);
+                }
+            }
+        })),
+        ..Default::default()
+    };
+    let report = encrypt(KEY, target(pack.path()), &options).unwrap();
+
+    let listed_keys = listed_keys.lock().unwrap();
+    assert_eq!(listed_keys.len(), report.encrypted_files);
+    assert!(listed_keys
+        .iter()
+        .all(|keys| *keys == report.encrypted_files));
+}
+
+#[test]
+fn encryption_cancelled_before_start_leaves_pack_as_is() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let options = EncryptOptions {
+        processing: ProcessingOptions {
+            jobs: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    options.processing.cancellation.cancel();
+
+    let result = encrypt(KEY, target(pack.path()), &options);
+    assert!(matches!(result, Err(PackEncryptionError::Cancelled(_))));
+    // contents.json written before encryption is removed again
+    assert_eq!(read_tree(pack.path()), original);
+}
+
+#[test]
+fn decrypted_pack_can_be_encrypted_again() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let first = encrypt(KEY, target(pack.path()), &encrypt_options()).unwrap();
+    decrypt(KEY, target(pack.path()), &DecryptOptions::default()).unwrap();
+    // Decrypted contents.json is left in the pack
+    assert!(pack.path().join("contents.json").is_file());
+
+    let second = encrypt(KEY, target(pack.path()), &encrypt_options()).unwrap();
+    assert_eq!(second.files, first.files);
+    assert_eq!(second.encrypted_files, first.encrypted_files);
+
+    let report = verify(KEY, &target(pack.path())).unwrap();
+    assert!(report.is_valid(), "{:?}", report);
+
+    decrypt(KEY, target(pack.path()), &DecryptOptions::default()).unwrap();
+    let mut decrypted = read_tree(pack.path());
+    decrypted.remove("contents.json");
+    assert_eq!(decrypted, original);
+}
+
+#[test]
+fn missing_contents_is_named_in_error() {
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let e = decrypt(KEY, target(pack.path()), &DecryptOptions::default()).unwrap_err();
+    assert!(
+        matches!(&e, PackEncryptionError::FileError(e) if e.path == "contents.json"),
+        "{:?}",
+        e
+    );
+    assert!(error_chain(&e).starts_with("Can't read file contents.json: "));
+}
@@ -0,0 +1,281 @@
+mod common;
+
+use common::{fixture_path, read_tree, FIXTURE_PACK, KEY};
+use kotik::packs::archive::encrypt_archive;
+use kotik::packs::encryption::decrypt::{decrypt_storage, DecryptOptions};
+use kotik::packs::encryption::encrypt::{encrypt_storage, EncryptOptions};
+use kotik::packs::pack_encryption::PackEncryptionError;
+use kotik::packs::storage::{MemoryStorage, PackStorage, SubfolderStorage, ZipStorage};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+use tempfile::TempDir;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+fn os_path(path: &str) -> PathBuf {
+    PathBuf::from(path.replace('/', MAIN_SEPARATOR_STR))
+}
+
+fn write_zip(path: &Path, files: &BTreeMap<String, Vec<u8>>) {
+    let mut writer = ZipWriter::new(File::create(path).unwrap());
+    for (name, content) in files {
+        writer
+            .start_file(name.as_str(), SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+#[test]
+fn memory_storage_entries() {
+    let storage = MemoryStorage::from(BTreeMap::from([
+        (String::from("manifest.json"), b"{}".to_vec()),
+        (String::from("textures/blocks/stone.png"), vec![1, 2, 3]),
+        (String::from("empty/"), Vec::new()),
+    ]));
+
+    assert_eq!(
+        storage.list_entries().unwrap(),
+        [
+            "empty/",
+            "textures/",
+            "textures/blocks/",
+            "manifest.json",
+            "textures/blocks/stone.png"
+        ]
+        .map(os_path)
+    );
+    assert!(storage.is_dir(Path::new("textures")));
+    assert!(storage.is_dir(Path::new("empty")));
+    assert!(!storage.is_dir(Path::new("manifest.json")));
+    assert!(storage.exists(Path::new("textures/blocks")));
+    assert!(!storage.exists(Path::new("texture")));
+    assert_eq!(
+        storage.size(&os_path("textures/blocks/stone.png")).unwrap(),
+        3
+    );
+    assert_eq!(
+        storage
+            .read_prefix(&os_path("textures/blocks/stone.png"), 2)
+            .unwrap(),
+        [1, 2]
+    );
+
+    storage.write(&os_path("texts/en_US.lang"), b"a=b").unwrap();
+    assert!(storage.is_dir(Path::new("texts")));
+    assert!(storage.write(Path::new("textures"), b"").is_err());
+    assert!(storage.read(Path::new("missing.json")).is_err());
+}
+
+#[test]
+fn subfolder_storage_entries() {
+    let storage = MemoryStorage::from(BTreeMap::from([
+        (String::from("rp/manifest.json"), b"{}".to_vec()),
+        (String::from("rp/textures/stone.png"), vec![1]),
+        (String::from("rp_other/manifest.json"), b"{}".to_vec()),
+    ]));
+    let pack = SubfolderStorage::new(&storage, "rp");
+
+    assert_eq!(
+        pack.list_entries().unwrap(),
+        ["textures/", "manifest.json", "textures/stone.png"].map(os_path)
+    );
+    pack.write(Path::new("contents.json"), b"x").unwrap();
+    assert_eq!(storage.read(&os_path("rp/contents.json")).unwrap(), b"x");
+}
+
+#[test]
+fn memory_storage_round_trip() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let storage = MemoryStorage::from(original.clone());
+
+    let options = EncryptOptions {
+        lint: true,
+        ..Default::default()
+    };
+    let report = encrypt_storage(KEY, &storage, &options).unwrap();
+    assert_eq!(report.files, original.len());
+    assert!(storage.exists(Path::new("contents.json")));
+    assert_ne!(
+        storage.read(&os_path("textures/blocks/stone.png")).unwrap(),
+        original["textures/blocks/stone.png"]
+    );
+
+    decrypt_storage(KEY, &storage, &DecryptOptions::default()).unwrap();
+    let mut decrypted = storage.into_files();
+    assert!(decrypted.remove("contents.json").is_some());
+    assert_eq!(decrypted, original);
+}
+
+#[test]
+fn archive_round_trip() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let files = original
+        .iter()
+        .flat_map(|(path, content)| {
+            ["resource/", "second/"].map(|root| (format!("{}{}", root, path), content.clone()))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let temp = TempDir::new().unwrap();
+    let archive_path = temp.path().join("addon.mcaddon");
+    write_zip(&archive_path, &files);
+
+    let (output_path, packs) = encrypt_archive(&archive_path, &EncryptOptions::default()).unwrap();
+    assert_eq!(output_path, temp.path().join("addon_encrypted.mcaddon"));
+    assert_eq!(packs.len(), 2);
+
+    let storage = ZipStorage::open(&output_path).unwrap();
+    for pack in &packs {
+        let pack_storage = SubfolderStorage::new(&storage, &pack.path);
+        assert!(pack_storage.exists(Path::new("contents.json")));
+        decrypt_storage(&pack.key, &pack_storage, &DecryptOptions::default()).unwrap();
+    }
+
+    // Source archive isn't changed
+    let source = ZipStorage::open(&archive_path).unwrap();
+    for (path, content) in &files {
+        assert_eq!(&source.read(&os_path(path)).unwrap(), content, "{}", path);
+    }
+
+    for (path, content) in &files {
+        assert_eq!(&storage.read(&os_path(path)).unwrap(), content, "{}", path);
+    }
+}
+
+#[test]
+fn archive_entries_escaping_it_are_rejected() {
+    let temp = TempDir::new().unwrap();
+    let archive_path = temp.path().join("evil.mcpack");
+    write_zip(
+        &archive_path,
+        &BTreeMap::from([(String::from("../evil.json"), b"{}".to_vec())]),
+    );
+
+    assert!(matches!(
+        ZipStorage::open(&archive_path),
+        Err(PackEncryptionError::ArchiveError(_))
+    ));
+}
+
+#[test]
+fn unsafe_paths_are_rejected_in_memory() {
+    use kotik::packs::contents::{ContentsRoot, ContentsRootItem};
+    use kotik::packs::encryption::encrypt::generate_contents_file;
+
+    let root = ContentsRoot {
+        version: 1,
+        content: vec![ContentsRootItem {
+            path: String::from("../outside.json"),
+            key: Some(String::from(KEY)),
+        }],
+    };
+    let storage = MemoryStorage::from(BTreeMap::from([(
+        String::from("contents.json"),
+        generate_contents_file(KEY, "uuid", &root).unwrap(),
+    )]));
+
+    assert!(matches!(
+        decrypt_storage(KEY, &storage, &DecryptOptions::default()),
+        Err(PackEncryptionError::UnsafePaths(_))
+    ));
+}
+
+#[test]
+fn archive_with_lying_size_in_header() {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file(
+            "manifest.json",
+            SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .large_file(true),
+        )
+        .unwrap();
+    writer.write_all(b"{}").unwrap();
+    let mut archive = writer.finish().unwrap().into_inner();
+
+    // Uncompressed size in zip64 extra field of the central directory says
+    // the file is almost 8 EiB, it must not be trusted
+    let central = archive
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .unwrap();
+    let extra = central
+        + 46
+        + archive[central + 28..central + 30]
+            .iter()
+            .rev()
+            .fold(0, |size, byte| size * 256 + *byte as usize);
+    assert_eq!(archive[extra..extra + 2], [1, 0]);
+    archive[extra + 4..extra + 12].copy_from_slice(&(u64::MAX / 2 - 16).to_le_bytes());
+
+    let storage = ZipStorage::from_bytes(&archive).unwrap();
+    assert_eq!(storage.read(Path::new("manifest.json")).unwrap(), b"{}");
+}
+
+#[test]
+fn nested_archives_are_encrypted() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let mut mcpack = ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, content) in &original {
+        mcpack
+            .start_file(path.as_str(), SimpleFileOptions::default())
+            .unwrap();
+        mcpack.write_all(content).unwrap();
+    }
+    let mcpack = mcpack.finish().unwrap().into_inner();
+
+    let mut files = original
+        .iter()
+        .map(|(path, content)| (format!("bp/{}", path), content.clone()))
+        .collect::<BTreeMap<_, _>>();
+    files.insert(String::from("packs/rp.mcpack"), mcpack.clone());
+    // Archive inside a pack is a pack file
+    files.insert(String::from("bp/extra/data.zip"), mcpack);
+
+    let temp = TempDir::new().unwrap();
+    let archive_path = temp.path().join("addon.mcaddon");
+    write_zip(&archive_path, &files);
+
+    let (output_path, packs) = encrypt_archive(&archive_path, &EncryptOptions::default()).unwrap();
+    assert_eq!(
+        packs
+            .iter()
+            .map(|pack| pack.path.as_str())
+            .collect::<Vec<_>>(),
+        ["bp", "packs/rp.mcpack"]
+    );
+
+    let storage = ZipStorage::open(&output_path).unwrap();
+    let nested =
+        ZipStorage::from_bytes(&storage.read(&os_path("packs/rp.mcpack")).unwrap()).unwrap();
+    assert!(nested.exists(Path::new("contents.json")));
+    decrypt_storage(&packs[1].key, &nested, &DecryptOptions::default()).unwrap();
+    for (path, content) in &original {
+        assert_eq!(&nested.read(&os_path(path)).unwrap(), content, "{}", path);
+    }
+}
+
+#[test]
+fn archive_without_packs() {
+    let temp = TempDir::new().unwrap();
+    let archive_path = temp.path().join("empty.mcaddon");
+    let mut not_pack = ZipWriter::new(Cursor::new(Vec::new()));
+    not_pack
+        .start_file("readme.txt", SimpleFileOptions::default())
+        .unwrap();
+    let not_pack = not_pack.finish().unwrap().into_inner();
+    write_zip(
+        &archive_path,
+        &BTreeMap::from([(String::from("textures.zip"), not_pack)]),
+    );
+
+    assert!(matches!(
+        encrypt_archive(&archive_path, &EncryptOptions::default()),
+        Err(PackEncryptionError::DataCollectionError(_))
+    ));
+}
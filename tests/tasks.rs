@@ -0,0 +1,129 @@
+mod common;
+
+use common::{copy_fixture, fixture_path, read_tree, FIXTURE_PACK, KEY};
+use kotik::packs::encryption::decrypt::DecryptOptions;
+use kotik::packs::encryption::encrypt::EncryptOptions;
+use kotik::packs::encryption::tasks::{
+    spawn_decrypt, spawn_decrypt_in_memory, spawn_encrypt, spawn_encrypt_in_memory,
+};
+use kotik::packs::manifest::parse_manifest;
+use kotik::packs::pack_encryption::PackEncryptionError;
+use kotik::packs::progress::{ProgressCallback, ProgressEvent, ProgressStage};
+use std::fs;
+use std::sync::mpsc::sync_channel;
+use std::sync::Mutex;
+use tokio_stream::StreamExt;
+
+#[tokio::test]
+async fn encrypt_with_progress_stream() {
+    let original = read_tree(&fixture_path(FIXTURE_PACK));
+    let pack = copy_fixture(FIXTURE_PACK);
+
+    let mut task = spawn_encrypt(KEY, pack.path(), EncryptOptions::default());
+    let mut events = Vec::new();
+    // Stream ends together with encryption
+    while let Some(event) = task.progress().next().await {
+        events.push(event);
+    }
+    let report = task.await.unwrap();
+
+    assert!(matches!(
+        events.first(),
+        Some(ProgressEvent::Started {
+            stage: ProgressStage::Encrypt,
+            ..
+        })
+    ));
+    let done = events
+        .iter()
+        .filter(|event| matches!(event, ProgressEvent::FileDone { .. }))
+        .count();
+    assert_eq!(done, report.encrypted_files);
+    assert!(matches!(
+        events.last(),
+        Some(ProgressEvent::Finished { failed: 0, .. })
+    ));
+
+    spawn_decrypt(KEY, pack.path(), DecryptOptions::default())
+        .await
+        .unwrap();
+    let mut decrypted = read_tree(pack.path());
+    decrypted.remove("contents.json");
+    assert_eq!(decrypted, original);
+}
+
+#[tokio::test]
+async fn caller_callback_still_gets_events() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let options = EncryptOptions {
+        progress: Some(ProgressCallback::new(move |event| {
+            let _ = sender.send(event.clone());
+        })),
+        ..Default::default()
+    };
+
+    // Progress stream isn't read at all
+    let report = spawn_encrypt(KEY, pack.path(), options).await.unwrap();
+    let done = receiver
+        .try_iter()
+        .filter(|event| matches!(event, ProgressEvent::FileDone { .. }))
+        .count();
+    assert_eq!(done, report.encrypted_files);
+}
+
+#[tokio::test]
+async fn cancel_task() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    // Encryption waits on its first event until the task is cancelled
+    let (resume, wait) = sync_channel::<()>(0);
+    let wait = Mutex::new(Some(wait));
+    let options = EncryptOptions {
+        progress: Some(ProgressCallback::new(move |_| {
+            if let Some(wait) = wait.lock().unwrap().take() {
+                wait.recv().unwrap();
+            }
+        })),
+        ..Default::default()
+    };
+
+    let task = spawn_encrypt(KEY, pack.path(), options);
+    task.cancel();
+    resume.send(()).unwrap();
+
+    assert!(matches!(
+        task.await,
+        Err(PackEncryptionError::Cancelled(errors)) if errors.is_empty()
+    ));
+}
+
+#[tokio::test]
+async fn encrypt_and_decrypt_in_memory() {
+    let mut original = read_tree(&fixture_path(FIXTURE_PACK));
+    let manifest = parse_manifest(
+        &fs::read_to_string(fixture_path(FIXTURE_PACK).join("manifest.json")).unwrap(),
+    )
+    .unwrap();
+
+    let encrypted =
+        spawn_encrypt_in_memory(KEY, original.clone(), manifest, EncryptOptions::default())
+            .await
+            .unwrap();
+    assert!(encrypted.contains_key("contents.json"));
+
+    let mut decrypted = spawn_decrypt_in_memory(KEY, encrypted, DecryptOptions::default())
+        .await
+        .unwrap();
+    decrypted.remove("contents.json");
+    // Manifest is written from the typed one
+    assert!(decrypted.remove("manifest.json").is_some());
+    original.remove("manifest.json");
+    assert_eq!(decrypted, original);
+}
+
+#[tokio::test]
+async fn missing_pack() {
+    let temp = tempfile::TempDir::new().unwrap();
+    let result = spawn_encrypt(KEY, temp.path(), EncryptOptions::default()).await;
+    assert!(matches!(result, Err(PackEncryptionError::ManifestError(_))));
+}
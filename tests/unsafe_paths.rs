@@ -0,0 +1,169 @@
+mod common;
+
+use common::{copy_fixture, FIXTURE_PACK, KEY, UUID};
+use kotik::packs::contents::{ContentsRoot, ContentsRootItem, UnsafePathReason};
+use kotik::packs::encryption::decrypt::{decrypt, DecryptOptions};
+use kotik::packs::encryption::encrypt::generate_contents_file;
+use kotik::packs::pack_encryption::PackEncryptionError;
+use kotik::packs::verify::verify;
+use kotik::utils::cipher::aes256_cbf8_encrypt;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+const FILE_KEY: &str = "fedcba9876543210fedcba9876543210";
+
+// Pack from a third party, with contents.json listing given paths
+fn write_contents(pack: &Path, paths: &[&str]) {
+    let root = ContentsRoot {
+        version: 1,
+        content: paths
+            .iter()
+            .map(|path| ContentsRootItem {
+                path: path.to_string(),
+                key: Some(FILE_KEY.to_string()),
+            })
+            .collect(),
+    };
+    let contents = generate_contents_file(KEY, UUID, &root).unwrap();
+    fs::write(pack.join("contents.json"), contents).unwrap();
+}
+
+// File outside of the pack, that malicious contents.json targets
+fn victim() -> (TempDir, std::path::PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("victim.txt");
+    fs::write(
+        &path,
+        aes256_cbf8_encrypt(FILE_KEY, b"secret".to_vec()).unwrap(),
+    )
+    .unwrap();
+    (dir, path)
+}
+
+fn assert_rejected(pack: &Path, path: &str, reason: UnsafePathReason) {
+    let contents = fs::read(pack.join("contents.json")).unwrap();
+
+    match decrypt(
+        KEY,
+        pack.as_os_str().to_os_string(),
+        &DecryptOptions::default(),
+    ) {
+        Err(PackEncryptionError::UnsafePaths(paths)) => {
+            assert_eq!(paths.len(), 1);
+            assert_eq!(paths[0].path, path);
+            assert_eq!(paths[0].reason, reason);
+        }
+        result => panic!("unsafe path {} is not rejected: {:?}", path, result),
+    }
+    // Nothing is written, not even decrypted contents.json
+    assert_eq!(fs::read(pack.join("contents.json")).unwrap(), contents);
+
+    let report = verify(KEY, &pack.as_os_str().to_os_string()).unwrap();
+    assert!(!report.is_valid());
+    assert_eq!(report.unsafe_paths.len(), 1);
+    assert_eq!(report.unsafe_paths[0].reason, reason);
+    assert!(!report.missing.iter().any(|missing| missing == path));
+}
+
+#[test]
+fn parent_directory_is_rejected() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let (victim_dir, victim) = victim();
+    let before = fs::read(&victim).unwrap();
+
+    // Pack and victim are both in the temp folder
+    let relative = Path::new("..")
+        .join(victim_dir.path().file_name().unwrap())
+        .join("victim.txt");
+    let relative = relative.to_str().unwrap().replace('\\', "/");
+    write_contents(pack.path(), &["manifest.json", &relative]);
+
+    assert_rejected(pack.path(), &relative, UnsafePathReason::ParentDirectory);
+    assert_eq!(fs::read(&victim).unwrap(), before);
+}
+
+#[test]
+fn parent_directory_inside_pack_is_rejected() {
+    // Even if it ends up inside the pack, .. is never generated by encrypt
+    let pack = copy_fixture(FIXTURE_PACK);
+    write_contents(pack.path(), &["textures/../manifest.json"]);
+    assert_rejected(
+        pack.path(),
+        "textures/../manifest.json",
+        UnsafePathReason::ParentDirectory,
+    );
+}
+
+#[test]
+fn absolute_path_is_rejected() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let (_victim_dir, victim) = victim();
+    let before = fs::read(&victim).unwrap();
+
+    let absolute = victim.to_str().unwrap().to_string();
+    write_contents(pack.path(), &[&absolute]);
+
+    assert_rejected(pack.path(), &absolute, UnsafePathReason::Absolute);
+    assert_eq!(fs::read(&victim).unwrap(), before);
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_escape_is_rejected() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let (victim_dir, victim) = victim();
+    let before = fs::read(&victim).unwrap();
+
+    std::os::unix::fs::symlink(victim_dir.path(), pack.path().join("textures/linked")).unwrap();
+    write_contents(pack.path(), &["textures/linked/victim.txt"]);
+
+    assert_rejected(
+        pack.path(),
+        "textures/linked/victim.txt",
+        UnsafePathReason::SymlinkEscape,
+    );
+    assert_eq!(fs::read(&victim).unwrap(), before);
+}
+
+#[cfg(unix)]
+#[test]
+fn dangling_symlink_is_rejected() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let (victim_dir, _victim) = victim();
+
+    let target = victim_dir.path().join("created.txt");
+    std::os::unix::fs::symlink(&target, pack.path().join("created.txt")).unwrap();
+    write_contents(pack.path(), &["created.txt"]);
+
+    assert_rejected(pack.path(), "created.txt", UnsafePathReason::SymlinkEscape);
+    assert!(!target.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_inside_pack_is_allowed() {
+    let pack = copy_fixture(FIXTURE_PACK);
+    let stone = pack.path().join("textures/blocks/stone.png");
+    let original = fs::read(&stone).unwrap();
+    fs::write(
+        &stone,
+        aes256_cbf8_encrypt(FILE_KEY, original.clone()).unwrap(),
+    )
+    .unwrap();
+
+    std::os::unix::fs::symlink(
+        pack.path().join("textures/blocks"),
+        pack.path().join("blocks"),
+    )
+    .unwrap();
+    write_contents(pack.path(), &["blocks/stone.png"]);
+
+    decrypt(
+        KEY,
+        pack.path().as_os_str().to_os_string(),
+        &DecryptOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(fs::read(&stone).unwrap(), original);
+}
@@ -0,0 +1,115 @@
+// Runs in Node.js, needs wasm-bindgen-cli:
+//   cargo test --target wasm32-unknown-unknown --no-default-features --features wasm --test wasm
+#![cfg(target_arch = "wasm32")]
+
+use kotik::packs::storage::{PackStorage, ZipStorage};
+use kotik::wasm::{
+    contents_uuid, decrypt_archive, decrypt_bytes, decrypt_contents_json, encrypt_archive,
+    encrypt_bytes, random_key,
+};
+use serde_json::Value;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use wasm_bindgen_test::wasm_bindgen_test;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+#[path = "../src/packs/fixtures.rs"]
+mod fixtures;
+use fixtures::{KEY, UUID};
+
+// There is no file system, so fixture is embedded
+macro_rules! fixture_files {
+    ($($path:literal),*) => {
+        [$(($path, include_bytes!(concat!("fixtures/resource_pack/", $path)) as &[u8])),*]
+    };
+}
+
+const FIXTURE: [(&str, &[u8]); 9] = fixture_files!(
+    "manifest.json",
+    "pack_icon.png",
+    "entity/sample.entity.json",
+    "sounds/sound_definitions.json",
+    "subpacks/low/textures/blocks/stone.png",
+    "texts/en_US.lang",
+    "texts/languages.json",
+    "textures/blocks/stone.png",
+    "textures/terrain_texture.json"
+);
+
+fn fixture_archive() -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in FIXTURE {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[wasm_bindgen_test]
+fn encrypt_then_decrypt_archive() {
+    let encrypted = encrypt_archive(&fixture_archive(), false).unwrap();
+    let packs: Value = serde_wasm_bindgen::from_value(encrypted.packs().unwrap()).unwrap();
+    assert_eq!(packs[0]["uuid"], UUID);
+    let key = packs[0]["key"].as_str().unwrap().to_owned();
+    assert_eq!(key.len(), 32);
+
+    let archive = encrypted.archive();
+    let storage = ZipStorage::from_bytes(&archive).unwrap();
+    let contents = storage.read(Path::new("contents.json")).unwrap();
+    assert_eq!(contents_uuid(&contents).unwrap(), UUID);
+    assert_ne!(
+        storage
+            .read(Path::new("textures/blocks/stone.png"))
+            .unwrap(),
+        FIXTURE[7].1
+    );
+
+    let decrypted = ZipStorage::from_bytes(&decrypt_archive(&archive, &key).unwrap()).unwrap();
+    for (name, content) in FIXTURE {
+        assert_eq!(
+            decrypted.read(Path::new(name)).unwrap(),
+            content,
+            "{}",
+            name
+        );
+    }
+
+    assert!(decrypt_archive(&archive, KEY).is_err());
+}
+
+#[wasm_bindgen_test]
+fn contents_lists_pack_files() {
+    let encrypted = encrypt_archive(&fixture_archive(), true).unwrap();
+    let packs: Value = serde_wasm_bindgen::from_value(encrypted.packs().unwrap()).unwrap();
+    let key = packs[0]["key"].as_str().unwrap();
+
+    let storage = ZipStorage::from_bytes(&encrypted.archive()).unwrap();
+    let contents = storage.read(Path::new("contents.json")).unwrap();
+    let root: Value =
+        serde_wasm_bindgen::from_value(decrypt_contents_json(key, &contents).unwrap()).unwrap();
+
+    let paths = root["content"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["path"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    for (name, _) in FIXTURE {
+        assert!(paths.contains(&name), "{} is missing", name);
+    }
+}
+
+#[wasm_bindgen_test]
+fn cipher_round_trip() {
+    let data = b"Bedrock pack file".to_vec();
+    let encrypted = encrypt_bytes(KEY, &data).unwrap();
+    assert_ne!(encrypted, data);
+    assert_eq!(decrypt_bytes(KEY, &encrypted).unwrap(), data);
+
+    assert_eq!(random_key().len(), 32);
+    assert_ne!(random_key(), random_key());
+    assert!(encrypt_bytes("short", &data).is_err());
+}